@@ -0,0 +1,300 @@
+use crate::filesystem::{self, ItemGroup, ItemKind};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+
+/// How candidates are ranked when planning a cleanup
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupPolicy {
+    /// Items whose newest file is the oldest go first
+    #[default]
+    Oldest,
+    /// Items freeing the most space go first
+    Largest,
+    /// Only items the caller reports as ignored on other peers, oldest first
+    IgnoredElsewhere,
+}
+
+/// One item to ignore and then delete, in the order it should be removed
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CleanupStep {
+    /// Path within the category, as accepted by the ignore and delete endpoints
+    pub folder_path: Vec<String>,
+    /// Indexes of the `folder_path` components that are raw names, to be sent along with it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
+    pub kind: ItemKind,
+    pub size_kb: u64,
+    /// Space freed by deleting this item
+    pub reclaimable_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newest_modified: Option<u64>,
+    /// True when the item is already covered by the category's .stignore
+    pub ignored: bool,
+}
+
+/// An item that can be removed on its own, with its path from the category root
+pub(crate) struct Candidate<'a> {
+    pub item: &'a ItemGroup,
+    pub path: Vec<String>,
+    /// Same as `path`, but using raw names for components that aren't valid UTF-8
+    pub raw_path: Vec<String>,
+}
+
+impl Candidate<'_> {
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.item.reclaimable_size_kb * 1024
+    }
+
+    pub fn newest_modified(&self) -> Option<u64> {
+        self.item.newest_modified.or(self.item.modified)
+    }
+
+    /// Indexes of the components of `raw_path` that are raw names
+    pub fn raw_components(&self) -> Vec<usize> {
+        self.path
+            .iter()
+            .zip(&self.raw_path)
+            .enumerate()
+            .filter(|(_, (name, raw_name))| name != raw_name)
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+fn candidate<'a>(item: &'a ItemGroup, path: &[String], raw_path: &[String]) -> Candidate<'a> {
+    let mut item_path = path.to_vec();
+    item_path.push(item.name.clone());
+    let mut item_raw_path = raw_path.to_vec();
+    item_raw_path.push(item.raw_name.clone().unwrap_or_else(|| item.name.clone()));
+
+    Candidate {
+        item,
+        path: item_path,
+        raw_path: item_raw_path,
+    }
+}
+
+/// Collects the items a cleanup would remove as a unit: every top-level file and
+/// directory, so a movie is never split from its subtitles or extras.
+/// Symlinks are never candidates.
+pub(crate) fn collect_candidates(items: &[ItemGroup]) -> Vec<Candidate<'_>> {
+    items
+        .iter()
+        .filter(|item| item.kind != ItemKind::Symlink)
+        .map(|item| candidate(item, &[], &[]))
+        .collect()
+}
+
+/// Collects leaf directories (e.g. seasons) and the files that sit outside of any
+/// leaf directory. Symlinks are never candidates.
+pub(crate) fn collect_leaf_candidates<'a>(
+    items: &'a [ItemGroup],
+    path: &[String],
+    raw_path: &[String],
+    candidates: &mut Vec<Candidate<'a>>,
+) {
+    for item in items {
+        match item.kind {
+            ItemKind::Dir if !item.leaf => {
+                let parent = candidate(item, path, raw_path);
+                collect_leaf_candidates(&item.items, &parent.path, &parent.raw_path, candidates)
+            }
+            ItemKind::Dir | ItemKind::File => candidates.push(candidate(item, path, raw_path)),
+            ItemKind::Symlink => {}
+        }
+    }
+}
+
+/// True when `path` is `prefix` or lies beneath it
+fn is_within(path: &[String], prefix: &[String], case_insensitive: bool) -> bool {
+    !prefix.is_empty()
+        && prefix.len() <= path.len()
+        && prefix
+            .iter()
+            .zip(path)
+            .all(|(a, b)| filesystem::names_match(OsStr::new(a), OsStr::new(b), case_insensitive))
+}
+
+/// Picks items from a scanned category, in policy order, until `needed_bytes`
+/// would be freed. `is_ignored` reports whether a path is already in .stignore.
+pub fn plan(
+    category: &ItemGroup,
+    policy: CleanupPolicy,
+    ignored_elsewhere: &[Vec<String>],
+    case_insensitive: bool,
+    needed_bytes: u64,
+    is_ignored: impl Fn(&[String]) -> bool,
+) -> Vec<CleanupStep> {
+    let mut candidates = collect_candidates(&category.items);
+
+    // Nothing is freed by removing items that only share space with others
+    candidates.retain(|candidate| candidate.reclaimable_bytes() > 0);
+
+    match policy {
+        CleanupPolicy::Largest => candidates.sort_by(|a, b| {
+            b.reclaimable_bytes()
+                .cmp(&a.reclaimable_bytes())
+                .then_with(|| a.path.cmp(&b.path))
+        }),
+        CleanupPolicy::Oldest | CleanupPolicy::IgnoredElsewhere => {
+            if policy == CleanupPolicy::IgnoredElsewhere {
+                candidates.retain(|candidate| {
+                    ignored_elsewhere
+                        .iter()
+                        .any(|prefix| is_within(&candidate.path, prefix, case_insensitive))
+                });
+            }
+            // Items without a modification time sort last
+            candidates.sort_by(|a, b| {
+                let a_time = a.newest_modified().unwrap_or(u64::MAX);
+                let b_time = b.newest_modified().unwrap_or(u64::MAX);
+                a_time.cmp(&b_time).then_with(|| a.path.cmp(&b.path))
+            });
+        }
+    }
+
+    let mut planned_bytes = 0;
+    let mut steps = vec![];
+    for candidate in candidates {
+        if planned_bytes >= needed_bytes {
+            break;
+        }
+        planned_bytes += candidate.reclaimable_bytes();
+
+        steps.push(CleanupStep {
+            ignored: is_ignored(&candidate.path),
+            raw_components: candidate.raw_components(),
+            kind: candidate.item.kind,
+            size_kb: candidate.item.size_kb,
+            reclaimable_bytes: candidate.reclaimable_bytes(),
+            newest_modified: candidate.newest_modified(),
+            folder_path: candidate.raw_path,
+        });
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, kind: ItemKind, size_kb: u64, modified: u64) -> ItemGroup {
+        serde_json::from_value(serde_json::json!({
+            "id": name,
+            "name": name,
+            "kind": kind,
+            "size_kb": size_kb,
+            "unique_size_kb": size_kb,
+            "reclaimable_size_kb": size_kb,
+            "items": [],
+            "leaf": kind == ItemKind::Dir,
+            "modified": modified,
+            "newest_modified": modified,
+        }))
+        .unwrap()
+    }
+
+    fn library() -> ItemGroup {
+        let show = filesystem::group_items(
+            "Show".to_string(),
+            "Show".to_string(),
+            vec![
+                item("Season 1", ItemKind::Dir, 300, 100),
+                item("Season 2", ItemKind::Dir, 100, 400),
+            ],
+        );
+        filesystem::group_items(
+            "tv".to_string(),
+            "TV".to_string(),
+            vec![
+                show,
+                item("notes.txt", ItemKind::File, 1, 50),
+                item("Special", ItemKind::Dir, 200, 300),
+                item("link", ItemKind::Symlink, 0, 10),
+            ],
+        )
+    }
+
+    fn paths(steps: &[CleanupStep]) -> Vec<Vec<String>> {
+        steps.iter().map(|step| step.folder_path.clone()).collect()
+    }
+
+    #[test]
+    fn plan_orders_candidates_by_policy() {
+        let library = library();
+
+        let oldest = plan(
+            &library,
+            CleanupPolicy::Oldest,
+            &[],
+            false,
+            u64::MAX,
+            |_| false,
+        );
+        // The show goes last, as its newest season is the newest item
+        assert_eq!(
+            paths(&oldest),
+            vec![
+                vec!["notes.txt".to_string()],
+                vec!["Special".to_string()],
+                vec!["Show".to_string()],
+            ]
+        );
+
+        // Stops as soon as enough space would be freed
+        let largest = plan(
+            &library,
+            CleanupPolicy::Largest,
+            &[],
+            false,
+            500 * 1024,
+            |_| false,
+        );
+        assert_eq!(
+            paths(&largest),
+            vec![vec!["Show".to_string()], vec!["Special".to_string()]]
+        );
+        assert_eq!(largest[0].reclaimable_bytes, 400 * 1024);
+    }
+
+    #[test]
+    fn plan_limits_ignored_elsewhere_to_listed_paths() {
+        let library = library();
+        let elsewhere = vec![
+            vec!["show".to_string()],
+            vec!["Special".to_string(), "Extras".to_string()],
+        ];
+
+        // Only part of Special is ignored elsewhere, so it isn't proposed
+        let steps = plan(
+            &library,
+            CleanupPolicy::IgnoredElsewhere,
+            &elsewhere,
+            true,
+            u64::MAX,
+            |path| path == ["Show"],
+        );
+        assert_eq!(paths(&steps), vec![vec!["Show".to_string()]]);
+        assert!(steps[0].ignored);
+    }
+
+    #[test]
+    fn plan_keeps_item_directories_whole() {
+        let movie = filesystem::group_items(
+            "Movie".to_string(),
+            "Movie".to_string(),
+            vec![
+                item("movie.mkv", ItemKind::File, 500, 100),
+                item("movie.nfo", ItemKind::File, 1, 100),
+                item("Subs", ItemKind::Dir, 2, 100),
+            ],
+        );
+        let library =
+            filesystem::group_items("movies".to_string(), "Movies".to_string(), vec![movie]);
+
+        let steps = plan(&library, CleanupPolicy::Largest, &[], false, 1, |_| false);
+        assert_eq!(paths(&steps), vec![vec!["Movie".to_string()]]);
+        assert_eq!(steps[0].reclaimable_bytes, 503 * 1024);
+    }
+}
@@ -1,4 +1,10 @@
-use serde::{Deserialize, Serialize};
+use crate::hashing;
+use crate::ignores;
+use crate::remote;
+use crate::retention;
+use crate::syncthing;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::HashMap;
 use std::fs;
 
 #[derive(Debug)]
@@ -11,6 +17,10 @@ pub enum ConfigError {
         filename: String,
         source: toml::de::Error,
     },
+    Invalid {
+        filename: String,
+        message: String,
+    },
 }
 
 impl std::fmt::Display for ConfigError {
@@ -22,6 +32,9 @@ impl std::fmt::Display for ConfigError {
             ConfigError::Parse { filename, source } => {
                 write!(f, "Unable to parse config file '{}': {}", filename, source)
             }
+            ConfigError::Invalid { filename, message } => {
+                write!(f, "Invalid config file '{}': {}", filename, message)
+            }
         }
     }
 }
@@ -31,6 +44,7 @@ impl std::error::Error for ConfigError {
         match self {
             ConfigError::FileRead { source, .. } => Some(source),
             ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::Invalid { .. } => None,
         }
     }
 }
@@ -40,6 +54,98 @@ pub struct Category {
     pub id: String,
     pub name: String,
     pub relative_path: String,
+    /// Match item names and ignore rules regardless of case, like Syncthing's `(?i)` prefix
+    #[serde(default)]
+    pub case_insensitive: bool,
+    /// Rules removing old items from this category, none by default
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+    /// ID of the Syncthing folder holding this category, defaults to the category ID
+    #[serde(default)]
+    pub syncthing_folder_id: Option<String>,
+    #[serde(default)]
+    pub ignore_backend: IgnoreBackend,
+    /// Type of the Syncthing folder holding this category
+    #[serde(default)]
+    pub folder_type: FolderType,
+}
+
+/// Syncthing folder types, named as in Syncthing's config
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FolderType {
+    #[default]
+    SendReceive,
+    SendOnly,
+    ReceiveOnly,
+    ReceiveEncrypted,
+}
+
+impl FolderType {
+    /// Whether local changes, including deletions, are sent to other devices
+    pub fn sends_changes(&self) -> bool {
+        matches!(self, FolderType::SendReceive | FolderType::SendOnly)
+    }
+}
+
+/// Where a category's ignore patterns are managed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IgnoreBackend {
+    /// Read and write the folder's .stignore directly
+    #[default]
+    File,
+    /// Go through Syncthing's REST API, which requires the `[syncthing]` section
+    Syncthing,
+}
+
+impl Category {
+    pub fn folder_id(&self) -> &str {
+        self.syncthing_folder_id.as_deref().unwrap_or(&self.id)
+    }
+}
+
+/// Whether retention rules only report violations or also remove them
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionMode {
+    /// Record the items that break a rule without touching them
+    #[default]
+    Report,
+    /// Ignore and then delete the items that break a rule
+    Enforce,
+}
+
+/// Rules deciding which items a category keeps, checked periodically in the background.
+/// Items are leaf directories (e.g. seasons) and files outside of them.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub mode: RetentionMode,
+    /// Keep only the newest N directories within each item (e.g. seasons per show).
+    /// Files next to them, such as artwork, don't count.
+    #[serde(default)]
+    pub keep_newest: Option<usize>,
+    /// Remove items whose newest file is older than this many days
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    /// Remove the oldest items until the category fits within this many gigabytes
+    #[serde(default)]
+    pub max_size_gb: Option<u64>,
+}
+
+/// How the directory scanner treats symbolic links
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    /// Leave symlinks out of listings entirely
+    Skip,
+    /// Follow symlinks out of the category, listing each target once. Links within the
+    /// category, or to a target already listed, are reported like `Report` does.
+    Follow,
+    /// List symlinks and their targets without following them
+    #[default]
+    Report,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -48,6 +154,49 @@ pub struct AgentConfig {
     pub port: u16,
     pub base_path: String,
     pub api_key: String,
+    /// Number of threads used to scan category directories, defaults to one per CPU
+    #[serde(default)]
+    pub scan_threads: Option<usize>,
+    /// Whether scans skip symlinks, follow them, or report them with their targets
+    #[serde(default)]
+    pub symlinks: SymlinkPolicy,
+    /// Minutes between retention checks, defaults to hourly
+    #[serde(default)]
+    pub retention_interval_minutes: Option<u64>,
+    /// Refuse deletes that Syncthing would send to other devices, unless the path
+    /// is ignored first or the request is forced
+    #[serde(default = "default_delete_guard")]
+    pub delete_guard: bool,
+}
+
+fn default_delete_guard() -> bool {
+    true
+}
+
+/// Connection details for the local Syncthing REST API
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncthingConfig {
+    /// Base URL of the Syncthing GUI, e.g. "http://127.0.0.1:8384"
+    pub url: String,
+    pub api_key: String,
+    /// Derive categories from Syncthing's folders on startup
+    #[serde(default)]
+    pub discover: Option<DiscoveryConfig>,
+    /// Seconds a folder's sync status is reused before asking Syncthing again, defaults to 10
+    #[serde(default)]
+    pub status_ttl_secs: Option<u64>,
+}
+
+/// Filters for the Syncthing folders turned into categories. Folders must be
+/// under `agent.base_path`, and explicitly configured categories take precedence.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DiscoveryConfig {
+    /// Only folders whose label starts with this
+    #[serde(default)]
+    pub label_prefix: Option<String>,
+    /// Only folders whose path is under this, relative to `agent.base_path`
+    #[serde(default)]
+    pub path_prefix: Option<String>,
 }
 
 // Parent struct holding the entire config file
@@ -55,20 +204,159 @@ pub struct AgentConfig {
 pub struct Data {
     pub(crate) agent: AgentConfig,
     pub(crate) categories: Vec<Category>,
+    #[serde(default)]
+    pub(crate) syncthing: Option<SyncthingConfig>,
+    /// File hashes shared by every request, never read from the config file
+    #[serde(skip)]
+    pub(crate) hash_cache: hashing::HashCache,
+    /// Recent retention runs, never read from the config file
+    #[serde(skip)]
+    pub(crate) retention_log: retention::RetentionLog,
+    /// Recently fetched Syncthing folder statuses, never read from the config file
+    #[serde(skip)]
+    pub(crate) folder_status_cache: syncthing::FolderStatusCache,
+    /// Locks serializing ignore changes per Syncthing folder, never read from the config file
+    #[serde(skip)]
+    pub(crate) ignore_locks: ignores::IgnoreLocks,
 }
 
-pub fn load_config(filename: &str) -> Result<Data, ConfigError> {
+impl Data {
+    /// Returns a Syncthing client when the `[syncthing]` section is configured
+    pub(crate) fn syncthing_client(&self) -> Option<syncthing::Client> {
+        self.syncthing.as_ref().map(syncthing::Client::new)
+    }
+}
+
+/// Settings of the controller itself, when the binary runs with `--controller`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ControllerConfig {
+    pub port: u16,
+    pub api_key: String,
+}
+
+/// An agent managed by the controller
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteAgentConfig {
+    /// Name used to pick the agent in fan-out requests
+    pub name: String,
+    /// Base URL of the agent, e.g. "http://10.0.0.2:3000"
+    pub url: String,
+    pub api_key: String,
+}
+
+// Parent struct holding the entire controller config file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ControllerData {
+    pub(crate) controller: ControllerConfig,
+    pub(crate) agents: Vec<RemoteAgentConfig>,
+    /// A client for each agent by name, built once the config is loaded rather than read from it
+    #[serde(skip)]
+    pub(crate) clients: HashMap<String, remote::AgentClient>,
+}
+
+impl ControllerData {
+    /// Builds the client of every configured agent, so requests share their connections
+    pub(crate) fn with_clients(mut self) -> ControllerData {
+        self.clients = self
+            .agents
+            .iter()
+            .map(|agent| {
+                let client = remote::AgentClient::new(&agent.url, &agent.api_key);
+                (agent.name.clone(), client)
+            })
+            .collect();
+        self
+    }
+}
+
+/// Turns Syncthing folders into categories, skipping folders that are filtered out,
+/// lie outside of `agent.base_path` or are already covered by an existing category
+pub fn discovered_categories(
+    folders: &[syncthing::FolderConfig],
+    agent: &AgentConfig,
+    discover: &DiscoveryConfig,
+    existing: &[Category],
+) -> Vec<Category> {
+    let base_path = std::path::Path::new(&agent.base_path);
+
+    folders
+        .iter()
+        .filter(|folder| {
+            discover
+                .label_prefix
+                .as_ref()
+                .is_none_or(|prefix| folder.label.starts_with(prefix.as_str()))
+        })
+        .filter(|folder| {
+            !existing
+                .iter()
+                .any(|category| category.id == folder.id || category.folder_id() == folder.id)
+        })
+        .filter_map(|folder| {
+            let relative_path = match std::path::Path::new(&folder.path).strip_prefix(base_path) {
+                Ok(relative_path) => relative_path,
+                Err(_) => {
+                    tracing::warn!(
+                        "Skipping Syncthing folder '{}' outside of {:?}: {:?}",
+                        folder.id,
+                        base_path,
+                        folder.path
+                    );
+                    return None;
+                }
+            };
+
+            if let Some(prefix) = &discover.path_prefix
+                && !relative_path.starts_with(prefix)
+            {
+                return None;
+            }
+
+            Some(Category {
+                id: folder.id.clone(),
+                name: match folder.label.is_empty() {
+                    true => folder.id.clone(),
+                    false => folder.label.clone(),
+                },
+                relative_path: relative_path.to_string_lossy().to_string(),
+                case_insensitive: false,
+                retention: None,
+                syncthing_folder_id: Some(folder.id.clone()),
+                ignore_backend: Default::default(),
+                folder_type: folder.folder_type,
+            })
+        })
+        .collect()
+}
+
+fn read_config<T: DeserializeOwned>(filename: &str) -> Result<T, ConfigError> {
     let contents = fs::read_to_string(filename).map_err(|source| ConfigError::FileRead {
         filename: filename.to_string(),
         source,
     })?;
 
-    let data: Data = toml::from_str(&contents).map_err(|source| ConfigError::Parse {
+    toml::from_str(&contents).map_err(|source| ConfigError::Parse {
         filename: filename.to_string(),
         source,
-    })?;
+    })
+}
 
-    Ok(data)
+pub fn load_config(filename: &str) -> Result<Data, ConfigError> {
+    read_config(filename)
+}
+
+/// Loads the controller config, which must name every agent uniquely since requests pick agents by name
+pub fn load_controller_config(filename: &str) -> Result<ControllerData, ConfigError> {
+    let data: ControllerData = read_config(filename)?;
+
+    let mut names = std::collections::HashSet::new();
+    if let Some(agent) = data.agents.iter().find(|agent| !names.insert(&agent.name)) {
+        return Err(ConfigError::Invalid {
+            filename: filename.to_string(),
+            message: format!("agent name '{}' is used more than once", agent.name),
+        });
+    }
+    Ok(data.with_clients())
 }
 
 #[cfg(test)]
@@ -96,6 +384,154 @@ mod tests {
         assert!(data.is_ok());
     }
 
+    #[test]
+    fn serde_scan_threads() {
+        let data: Data = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+           scan_threads = 8
+           symlinks = "follow"
+
+           [[categories]]
+           id = "category_a"
+           name = "Category A"
+           relative_path = "a/"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(data.agent.scan_threads, Some(8));
+        assert_eq!(data.agent.symlinks, SymlinkPolicy::Follow);
+    }
+
+    #[test]
+    fn serde_retention_policy() {
+        let data: Data = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+           [[categories]]
+           id = "category_a"
+           name = "Category A"
+           relative_path = "a/"
+
+           [categories.retention]
+           keep_newest = 2
+           max_age_days = 180
+
+           [[categories]]
+           id = "category_b"
+           name = "Category B"
+           relative_path = "b/"
+        "#,
+        )
+        .unwrap();
+
+        let retention = data.categories[0].retention.as_ref().unwrap();
+        assert_eq!(retention.mode, RetentionMode::Report);
+        assert_eq!(retention.keep_newest, Some(2));
+        assert_eq!(retention.max_age_days, Some(180));
+        assert_eq!(retention.max_size_gb, None);
+        assert!(data.categories[1].retention.is_none());
+    }
+
+    #[test]
+    fn serde_syncthing_config() {
+        let data: Data = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/path/to/stuff"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+           [syncthing]
+           url = "http://127.0.0.1:8384"
+           api_key = "abc123"
+
+           [[categories]]
+           id = "category_a"
+           name = "Category A"
+           relative_path = "a/"
+           syncthing_folder_id = "abcde-12345"
+           ignore_backend = "syncthing"
+           folder_type = "receiveonly"
+
+           [[categories]]
+           id = "category_b"
+           name = "Category B"
+           relative_path = "b/"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(data.syncthing.unwrap().url, "http://127.0.0.1:8384");
+        assert_eq!(data.categories[0].folder_id(), "abcde-12345");
+        assert_eq!(data.categories[1].folder_id(), "category_b");
+        assert_eq!(data.categories[0].ignore_backend, IgnoreBackend::Syncthing);
+        assert_eq!(data.categories[1].ignore_backend, IgnoreBackend::File);
+        assert_eq!(data.categories[0].folder_type, FolderType::ReceiveOnly);
+        assert!(!data.categories[0].folder_type.sends_changes());
+    }
+
+    #[test]
+    fn discovered_categories_merge_with_config() {
+        let data: Data = toml::from_str(
+            r#"
+           [agent]
+           port = 3000
+           name = "Agent Smith"
+           base_path = "/srv"
+           api_key = "550e8400-e29b-41d4-a716-446655440000"
+
+           [syncthing]
+           url = "http://127.0.0.1:8384"
+           api_key = "abc123"
+
+           [syncthing.discover]
+           label_prefix = "Media"
+           path_prefix = "media"
+
+           [[categories]]
+           id = "movies"
+           name = "Movies"
+           relative_path = "media/movies"
+           syncthing_folder_id = "abcde-12345"
+        "#,
+        )
+        .unwrap();
+
+        let folder = |id: &str, label: &str, path: &str| syncthing::FolderConfig {
+            id: id.to_string(),
+            label: label.to_string(),
+            path: path.to_string(),
+            folder_type: FolderType::ReceiveOnly,
+        };
+        let folders = vec![
+            folder("abcde-12345", "Media Movies", "/srv/media/movies"),
+            folder("fghij-67890", "Media TV", "/srv/media/tv"),
+            folder("klmno-13579", "Media Music", "/srv/music"),
+            folder("pqrst-24680", "Photos", "/srv/media/photos"),
+            folder("uvwxy-11223", "Media Books", "/elsewhere/books"),
+        ];
+
+        let discover = data.syncthing.as_ref().unwrap().discover.as_ref().unwrap();
+        let categories = discovered_categories(&folders, &data.agent, discover, &data.categories);
+        assert_eq!(categories.len(), 1);
+        assert_eq!(categories[0].id, "fghij-67890");
+        assert_eq!(categories[0].name, "Media TV");
+        assert_eq!(categories[0].relative_path, "media/tv");
+        assert_eq!(categories[0].folder_id(), "fghij-67890");
+        assert_eq!(categories[0].folder_type, FolderType::ReceiveOnly);
+    }
+
     #[test]
     fn serde_invalid_config() {
         let data: Result<Data, toml::de::Error> = toml::from_str(
@@ -136,6 +572,65 @@ relative_path = "test/"
         assert_eq!(data.agent.name, "Test Agent");
         assert_eq!(data.categories.len(), 1);
         assert_eq!(data.categories[0].id, "test_category");
+        assert_eq!(data.agent.symlinks, SymlinkPolicy::Report);
+        assert!(data.agent.delete_guard);
+        assert_eq!(data.categories[0].folder_type, FolderType::SendReceive);
+    }
+
+    #[test]
+    fn serde_controller_config() {
+        let data: ControllerData = toml::from_str(
+            r#"
+           [controller]
+           port = 4000
+           api_key = "controller-key"
+
+           [[agents]]
+           name = "agent1"
+           url = "http://127.0.0.1:3001"
+           api_key = "agent1-key"
+
+           [[agents]]
+           name = "agent2"
+           url = "http://127.0.0.1:3002"
+           api_key = "agent2-key"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(data.controller.port, 4000);
+        assert_eq!(data.agents.len(), 2);
+        assert_eq!(data.agents[1].name, "agent2");
+        assert_eq!(data.agents[1].url, "http://127.0.0.1:3002");
+    }
+
+    #[test]
+    fn load_controller_config_rejects_duplicate_agent_names() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file
+            .write_all(
+                br#"
+                [controller]
+                port = 4000
+                api_key = "controller-key"
+
+                [[agents]]
+                name = "agent1"
+                url = "http://127.0.0.1:3001"
+                api_key = "agent1-key"
+
+                [[agents]]
+                name = "agent1"
+                url = "http://127.0.0.1:3002"
+                api_key = "agent2-key"
+                "#,
+            )
+            .unwrap();
+
+        match load_controller_config(temp_file.path().to_str().unwrap()).unwrap_err() {
+            ConfigError::Invalid { message, .. } => assert!(message.contains("agent1")),
+            _ => panic!("Expected Invalid error"),
+        }
     }
 
     #[test]
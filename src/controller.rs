@@ -0,0 +1,544 @@
+use crate::config::{ControllerData, RemoteAgentConfig};
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::filesystem::{ItemGroup, ItemKind, match_key};
+use crate::models::*;
+use crate::remote::{AgentClient, RemoteError};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// An item while merging listings, with its size on every agent holding it
+#[derive(Default)]
+struct MergeNode {
+    /// Name as spelled by the first agent listing the item
+    name: String,
+    kind: Option<ItemKind>,
+    sizes: BTreeMap<usize, u64>,
+    /// Children keyed by their normalized name
+    children: BTreeMap<String, MergeNode>,
+}
+
+impl MergeNode {
+    /// Adds the children of an agent's item, recursively
+    fn insert(&mut self, agent: usize, item: &ItemGroup) {
+        for child in &item.items {
+            let key = self.child_key(agent, &child.name);
+            let node = self.children.entry(key).or_default();
+            if node.name.is_empty() {
+                node.name = child.name.clone();
+            }
+            node.kind.get_or_insert(child.kind);
+            node.sizes.insert(agent, child.size_kb);
+            node.insert(agent, child);
+        }
+    }
+
+    /// Finds the child an agent's item merges into. Names equal once normalized always
+    /// merge, and names differing only in case merge unless the agent lists both.
+    fn child_key(&self, agent: usize, name: &str) -> String {
+        let key = match_key(name, false);
+        if self.children.contains_key(&key) {
+            return key;
+        }
+
+        let folded = match_key(name, true);
+        self.children
+            .iter()
+            .find(|(other, node)| {
+                !node.sizes.contains_key(&agent) && match_key(other, true) == folded
+            })
+            .map(|(other, _)| other.clone())
+            .unwrap_or(key)
+    }
+
+    /// Collects the path of every item below this one
+    fn paths(&self, prefix: &[String], paths: &mut Vec<Vec<String>>) {
+        for child in self.children.values() {
+            let mut path = prefix.to_vec();
+            path.push(child.name.clone());
+            child.paths(&path, paths);
+            paths.push(path);
+        }
+    }
+}
+
+/// Ignored paths reported by an agent, keyed by category ID and folder path
+type IgnoredPaths = HashSet<(String, Vec<String>)>;
+
+/// Turns the children of a merge node into items, with a state for every listed agent
+fn merged_items(
+    node: &MergeNode,
+    category_id: &str,
+    prefix: &[String],
+    agents: &[(usize, String, IgnoredPaths)],
+) -> Vec<MergedItem> {
+    node.children
+        .values()
+        .map(|child| {
+            let mut path = prefix.to_vec();
+            path.push(child.name.clone());
+
+            MergedItem {
+                name: child.name.clone(),
+                kind: child.kind.unwrap_or(ItemKind::Dir),
+                agents: agents
+                    .iter()
+                    .map(|(index, agent, ignored)| AgentItemState {
+                        agent: agent.clone(),
+                        present: child.sizes.contains_key(index),
+                        ignored: ignored.contains(&(category_id.to_string(), path.clone())),
+                        size_kb: child.sizes.get(index).copied(),
+                    })
+                    .collect(),
+                items: merged_items(child, category_id, &path, agents),
+            }
+        })
+        .collect()
+}
+
+/// Runs a request against each of the agents at once, returning the results in agent order
+async fn query_agents<T, F, Fut>(
+    data: &ControllerData,
+    agents: &[RemoteAgentConfig],
+    query: F,
+) -> Vec<Result<T, String>>
+where
+    T: Send + 'static,
+    F: Fn(AgentClient) -> Fut,
+    Fut: Future<Output = Result<T, RemoteError>> + Send + 'static,
+{
+    let handles: Vec<_> = agents
+        .iter()
+        .map(|agent| {
+            let handle = data
+                .clients
+                .get(&agent.name)
+                .map(|client| tokio::spawn(query(client.clone())));
+            (agent, handle)
+        })
+        .collect();
+
+    let mut results = vec![];
+    for (agent, handle) in handles {
+        results.push(match handle {
+            Some(handle) => match handle.await {
+                Ok(result) => result.map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            },
+            None => Err(format!("Agent '{}' has no client", agent.name)),
+        });
+    }
+    results
+}
+
+// GET categories
+// Returns the categories of every agent merged together, showing which agents have,
+// lack or ignore each item
+pub async fn category_list(State(data): State<ControllerData>) -> impl IntoResponse {
+    let listings = query_agents(&data, &data.agents, |client| async move {
+        client.categories().await
+    })
+    .await;
+
+    let mut summaries: Vec<AgentSummary> = data
+        .agents
+        .iter()
+        .map(|agent| AgentSummary {
+            name: agent.name.clone(),
+            url: agent.url.clone(),
+            error: None,
+        })
+        .collect();
+
+    // Categories keep the order the agents list them in
+    let mut categories: Vec<(String, String, MergeNode)> = vec![];
+    for (index, listing) in listings.into_iter().enumerate() {
+        let listing = match listing {
+            Ok(listing) => listing,
+            Err(err) => {
+                tracing::warn!(
+                    "Unable to list agent '{}': {}",
+                    data.agents[index].name,
+                    err
+                );
+                summaries[index].error = Some(err);
+                continue;
+            }
+        };
+
+        for category in &listing.items {
+            let position = match categories.iter().position(|(id, ..)| *id == category.id) {
+                Some(position) => position,
+                None => {
+                    categories.push((
+                        category.id.clone(),
+                        category.name.clone(),
+                        MergeNode::default(),
+                    ));
+                    categories.len() - 1
+                }
+            };
+            categories[position].2.insert(index, category);
+        }
+    }
+
+    // Ask the reachable agents about every merged path, including the ones they lack
+    let mut items = vec![];
+    for (id, _, node) in &categories {
+        let mut paths = vec![];
+        node.paths(&[], &mut paths);
+        items.extend(paths.into_iter().map(|folder_path| IgnoreStatusRequest {
+            category_id: id.clone(),
+            folder_path,
+            raw_components: vec![],
+        }));
+    }
+    let request = BulkIgnoreStatusRequest { items };
+
+    let reachable: Vec<RemoteAgentConfig> = data
+        .agents
+        .iter()
+        .zip(&summaries)
+        .filter(|(_, summary)| summary.error.is_none())
+        .map(|(agent, _)| agent.clone())
+        .collect();
+    let statuses = query_agents(&data, &reachable, |client| {
+        let request = request.clone();
+        async move { client.ignore_status_bulk(&request).await }
+    })
+    .await;
+
+    let mut agents = vec![];
+    let reachable_indexes: Vec<usize> = (0..summaries.len())
+        .filter(|index| summaries[*index].error.is_none())
+        .collect();
+    for (index, status) in reachable_indexes.into_iter().zip(statuses) {
+        let ignored = match status {
+            Ok(status) => status
+                .items
+                .into_iter()
+                .filter(|item| item.ignored)
+                .map(|item| (item.category_id, item.folder_path))
+                .collect(),
+            Err(err) => {
+                tracing::warn!(
+                    "Unable to check ignores of agent '{}': {}",
+                    data.agents[index].name,
+                    err
+                );
+                summaries[index].error = Some(format!("Unable to check ignores: {}", err));
+                HashSet::new()
+            }
+        };
+        agents.push((index, data.agents[index].name.clone(), ignored));
+    }
+
+    let categories = categories
+        .iter()
+        .map(|(id, name, node)| MergedCategory {
+            id: id.clone(),
+            name: name.clone(),
+            items: merged_items(node, id, &[], &agents),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(ControllerListingResponse {
+            agents: summaries,
+            categories,
+        }),
+    )
+}
+
+/// Helper function to forward a request to the chosen agents and collect their responses
+async fn fan_out<T>(
+    data: &ControllerData,
+    agent_names: &[String],
+    path: &str,
+    payload: T,
+) -> Result<Json<FanOutResponse>, ApiError>
+where
+    T: Serialize + Clone + Send + Sync + 'static,
+{
+    if agent_names.is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            "No agents selected",
+        ));
+    }
+
+    // Each agent is asked once, however often the request names it
+    let mut seen = HashSet::new();
+    let mut handles = vec![];
+    for name in agent_names.iter().filter(|name| seen.insert(*name)) {
+        let handle = data.clients.get(name).cloned().map(|client| {
+            let payload = payload.clone();
+            let path = path.to_string();
+            tokio::spawn(async move { client.forward(&path, &payload).await })
+        });
+        handles.push((name.clone(), handle));
+    }
+
+    let mut results = vec![];
+    for (agent, handle) in handles {
+        let result = match handle {
+            Some(handle) => match handle.await {
+                Ok(result) => result.map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            },
+            None => Err(format!("Agent '{}' isn't configured", agent)),
+        };
+
+        results.push(match result {
+            Ok((status, response)) => AgentResult {
+                agent,
+                status: Some(status.as_u16()),
+                response: Some(response),
+                error: None,
+            },
+            Err(err) => {
+                tracing::warn!("Unable to forward {} to agent '{}': {}", path, agent, err);
+                AgentResult {
+                    agent,
+                    status: None,
+                    response: None,
+                    error: Some(err),
+                }
+            }
+        });
+    }
+
+    Ok(Json(FanOutResponse { results }))
+}
+
+// POST ignore
+// Adds the folder path to the ignores of the chosen agents
+pub async fn post_ignore(
+    State(data): State<ControllerData>,
+    ApiJson(payload): ApiJson<ControllerIgnoreRequest>,
+) -> Result<Json<FanOutResponse>, ApiError> {
+    tracing::info!(
+        "Forwarding ignore of {:?} in category '{}' to agents {:?}",
+        payload.request.folder_path,
+        payload.request.category_id,
+        payload.agents
+    );
+    fan_out(&data, &payload.agents, "/api/v1/ignore", payload.request).await
+}
+
+// POST delete
+// Deletes the folder path on the chosen agents, each applying its own delete guard
+pub async fn post_delete(
+    State(data): State<ControllerData>,
+    ApiJson(payload): ApiJson<ControllerDeleteRequest>,
+) -> Result<Json<FanOutResponse>, ApiError> {
+    tracing::info!(
+        "Forwarding delete of {:?} in category '{}' to agents {:?}",
+        payload.request.folder_path,
+        payload.request.category_id,
+        payload.agents
+    );
+    fan_out(&data, &payload.agents, "/api/v1/delete", payload.request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ControllerConfig;
+    use crate::tasks::tests::{create_test_data, serve_test_agent};
+    use axum::Router;
+    use axum_test::TestServer;
+    use tempfile::TempDir;
+
+    const API_KEY: &str = "controller-test-key";
+
+    /// Serves a test agent over TCP, returning its config along with the directory it serves
+    async fn spawn_agent(name: &str) -> (RemoteAgentConfig, TempDir) {
+        let (data, temp_dir) = create_test_data();
+        let api_key = data.agent.api_key.clone();
+        let url = serve_test_agent(data).await;
+
+        let agent = RemoteAgentConfig {
+            name: name.to_string(),
+            url,
+            api_key,
+        };
+        (agent, temp_dir)
+    }
+
+    fn create_controller(agents: Vec<RemoteAgentConfig>) -> TestServer {
+        let data = ControllerData {
+            controller: ControllerConfig {
+                port: 4000,
+                api_key: API_KEY.to_string(),
+            },
+            agents,
+            clients: Default::default(),
+        }
+        .with_clients();
+        let app = Router::new()
+            .route("/api/v1/categories", axum::routing::get(category_list))
+            .route("/api/v1/ignore", axum::routing::post(post_ignore))
+            .route("/api/v1/delete", axum::routing::post(post_delete))
+            .layer(axum::middleware::from_fn_with_state(
+                data.controller.api_key.clone(),
+                crate::auth_middleware,
+            ))
+            .with_state(data);
+        TestServer::new(app).unwrap()
+    }
+
+    fn find<'a>(items: &'a [MergedItem], name: &str) -> &'a MergedItem {
+        items.iter().find(|item| item.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn merged_listing_shows_missing_and_ignored_items() {
+        let (agent1, _dir1) = spawn_agent("agent1").await;
+        let (agent2, dir2) = spawn_agent("agent2").await;
+        std::fs::remove_dir_all(dir2.path().join("movies/Movie 2 (2024)")).unwrap();
+        std::fs::write(dir2.path().join("movies/.stignore"), "/Movie 1 (2023)\n").unwrap();
+        let offline = RemoteAgentConfig {
+            name: "offline".to_string(),
+            url: "http://127.0.0.1:9".to_string(),
+            api_key: "unused".to_string(),
+        };
+        let server = create_controller(vec![agent1, agent2, offline]);
+
+        let response = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", API_KEY)
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: ControllerListingResponse = response.json();
+
+        assert!(json.agents[0].error.is_none());
+        assert!(json.agents[1].error.is_none());
+        assert!(json.agents[2].error.is_some());
+
+        let movies = json.categories.iter().find(|c| c.id == "movies").unwrap();
+        let movie2 = find(&movies.items, "Movie 2 (2024)");
+        assert_eq!(movie2.agents.len(), 2);
+        assert!(movie2.agents[0].present);
+        assert!(!movie2.agents[1].present);
+        assert!(!movie2.agents[1].ignored);
+        // Contents only one agent holds are still merged in
+        assert!(!find(&movie2.items, "Movie 2 (2024).mp4").agents[1].present);
+
+        let movie1 = find(&movies.items, "Movie 1 (2023)");
+        assert!(movie1.agents[1].present);
+        assert!(movie1.agents[1].ignored);
+        assert!(!movie1.agents[0].ignored);
+        assert_eq!(movie1.agents[0].size_kb, movie1.agents[1].size_kb);
+    }
+
+    #[tokio::test]
+    async fn merged_listing_matches_names_across_spellings() {
+        let (agent1, dir1) = spawn_agent("agent1").await;
+        let (agent2, dir2) = spawn_agent("agent2").await;
+        std::fs::create_dir(dir1.path().join("movies/Ame\u{301}lie (2001)")).unwrap();
+        std::fs::create_dir(dir2.path().join("movies/Am\u{e9}lie (2001)")).unwrap();
+        std::fs::rename(
+            dir2.path().join("movies/Movie 2 (2024)"),
+            dir2.path().join("movies/MOVIE 2 (2024)"),
+        )
+        .unwrap();
+        // An agent listing two names differing only in case keeps both
+        std::fs::create_dir(dir2.path().join("movies/movie 1 (2023)")).unwrap();
+        let server = create_controller(vec![agent1, agent2]);
+
+        let response = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", API_KEY)
+            .await;
+        let json: ControllerListingResponse = response.json();
+        let movies = json.categories.iter().find(|c| c.id == "movies").unwrap();
+        assert_eq!(movies.items.len(), 4);
+
+        let amelie = find(&movies.items, "Ame\u{301}lie (2001)");
+        assert!(amelie.agents.iter().all(|agent| agent.present));
+        let movie2 = find(&movies.items, "Movie 2 (2024)");
+        assert!(movie2.agents.iter().all(|agent| agent.present));
+        assert!(find(&movie2.items, "Movie 2 (2024).mp4").agents[1].present);
+        assert!(!find(&movies.items, "movie 1 (2023)").agents[0].present);
+    }
+
+    #[tokio::test]
+    async fn fan_out_reaches_only_chosen_agents() {
+        let (agent1, dir1) = spawn_agent("agent1").await;
+        let (agent2, dir2) = spawn_agent("agent2").await;
+        let server = create_controller(vec![agent1, agent2]);
+
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", API_KEY)
+            .json(&serde_json::json!({
+                "agents": ["agent2", "agent2"],
+                "category_id": "movies",
+                "folder_path": ["Movie 1 (2023)"],
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: FanOutResponse = response.json();
+        assert_eq!(json.results.len(), 1);
+        assert_eq!(json.results[0].status, Some(200));
+
+        let stignore =
+            |dir: &TempDir| std::fs::read_to_string(dir.path().join("movies/.stignore")).unwrap();
+        assert!(stignore(&dir2).contains("/Movie 1 (2023)"));
+        assert!(!stignore(&dir1).contains("/Movie 1 (2023)"));
+
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", API_KEY)
+            .json(&serde_json::json!({
+                "agents": ["agent2", "agent3"],
+                "category_id": "movies",
+                "folder_path": ["Movie 1 (2023)"],
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: FanOutResponse = response.json();
+        assert_eq!(json.results[0].status, Some(200));
+        assert!(json.results[1].error.is_some());
+
+        assert!(!dir2.path().join("movies/Movie 1 (2023)").exists());
+        assert!(dir1.path().join("movies/Movie 1 (2023)").exists());
+
+        // Each agent's delete guard still applies to forwarded deletes
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", API_KEY)
+            .json(&serde_json::json!({
+                "agents": ["agent1"],
+                "category_id": "movies",
+                "folder_path": ["Movie 2 (2024)"],
+            }))
+            .await;
+        let json: FanOutResponse = response.json();
+        assert_eq!(json.results[0].status, Some(409));
+        assert!(dir1.path().join("movies/Movie 2 (2024)").exists());
+
+        server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", API_KEY)
+            .json(&serde_json::json!({
+                "agents": [],
+                "category_id": "movies",
+                "folder_path": ["Movie 2 (2024)"],
+            }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        // Bodies missing a field are rejected before reaching any agent
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", API_KEY)
+            .json(&serde_json::json!({ "agents": ["agent1"], "category_id": "movies" }))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::InvalidRequest);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::filesystem::{self, ItemGroup, ItemKind};
+use serde::{Deserialize, Serialize};
+
+/// An item that only one of the two agents has, reported at the topmost missing path
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DiffItem {
+    pub folder_path: Vec<String>,
+    pub kind: ItemKind,
+    pub size_kb: u64,
+}
+
+/// A file both agents have, but with different sizes
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct SizeMismatch {
+    pub folder_path: Vec<String>,
+    pub here_size_kb: u64,
+    pub there_size_kb: u64,
+}
+
+/// A path ignored by only one of the two agents, reported at the topmost differing path
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct IgnoreMismatch {
+    pub folder_path: Vec<String>,
+    pub ignored_here: bool,
+    pub ignored_there: bool,
+}
+
+/// Differences between the contents of a category on two agents
+#[derive(Debug, Default)]
+pub(crate) struct TreeDiff {
+    pub only_here: Vec<DiffItem>,
+    pub only_there: Vec<DiffItem>,
+    pub size_mismatches: Vec<SizeMismatch>,
+}
+
+/// Finds the item with the given name, compared the way the category compares names
+fn find<'a>(items: &'a [ItemGroup], name: &str, case_insensitive: bool) -> Option<&'a ItemGroup> {
+    let key = filesystem::match_key(name, case_insensitive);
+    items
+        .iter()
+        .find(|item| filesystem::match_key(&item.name, case_insensitive) == key)
+}
+
+/// Contents of the named item, or nothing when there is no such item
+fn children<'a>(items: &'a [ItemGroup], name: &str, case_insensitive: bool) -> &'a [ItemGroup] {
+    find(items, name, case_insensitive).map_or(&[], |item| &item.items)
+}
+
+/// Reduces every component of a path to its comparison form, see `filesystem::match_key`
+pub(crate) fn path_key(path: &[String], case_insensitive: bool) -> Vec<String> {
+    path.iter()
+        .map(|name| filesystem::match_key(name, case_insensitive))
+        .collect()
+}
+
+fn diff_item(item: &ItemGroup, path: Vec<String>) -> DiffItem {
+    DiffItem {
+        folder_path: path,
+        kind: item.kind,
+        size_kb: item.size_kb,
+    }
+}
+
+/// Compares two item trees by name, ignoring Unicode normalization and optionally case.
+/// Directories are only compared through their contents, so a missing file isn't also
+/// reported as a size change of every parent.
+pub(crate) fn compare(here: &[ItemGroup], there: &[ItemGroup], case_insensitive: bool) -> TreeDiff {
+    let mut diff = TreeDiff::default();
+    compare_items(here, there, &[], case_insensitive, &mut diff);
+    diff
+}
+
+fn compare_items(
+    here: &[ItemGroup],
+    there: &[ItemGroup],
+    prefix: &[String],
+    case_insensitive: bool,
+    diff: &mut TreeDiff,
+) {
+    for item in here {
+        let mut path = prefix.to_vec();
+        path.push(item.name.clone());
+
+        match find(there, &item.name, case_insensitive) {
+            None => diff.only_here.push(diff_item(item, path)),
+            Some(other) if item.kind != other.kind => {
+                // Something else entirely under the same name
+                diff.only_here.push(diff_item(item, path.clone()));
+                diff.only_there.push(diff_item(other, path));
+            }
+            Some(other) => {
+                if item.kind == ItemKind::File && item.size_kb != other.size_kb {
+                    diff.size_mismatches.push(SizeMismatch {
+                        folder_path: path.clone(),
+                        here_size_kb: item.size_kb,
+                        there_size_kb: other.size_kb,
+                    });
+                }
+                compare_items(&item.items, &other.items, &path, case_insensitive, diff);
+            }
+        }
+    }
+
+    for other in there {
+        if find(here, &other.name, case_insensitive).is_none() {
+            let mut path = prefix.to_vec();
+            path.push(other.name.clone());
+            diff.only_there.push(diff_item(other, path));
+        }
+    }
+}
+
+/// Collects every path found in either tree, parents before their contents. Names that
+/// compare equal are listed once, as spelled here when this side has them.
+pub(crate) fn union_paths(
+    here: &[ItemGroup],
+    there: &[ItemGroup],
+    case_insensitive: bool,
+) -> Vec<Vec<String>> {
+    let mut paths = vec![];
+    union_items(here, there, &[], case_insensitive, &mut paths);
+    paths
+}
+
+fn union_items(
+    here: &[ItemGroup],
+    there: &[ItemGroup],
+    prefix: &[String],
+    case_insensitive: bool,
+    paths: &mut Vec<Vec<String>>,
+) {
+    let mut names: Vec<(String, &str)> = here
+        .iter()
+        .chain(there)
+        .map(|item| {
+            let key = filesystem::match_key(&item.name, case_insensitive);
+            (key, item.name.as_str())
+        })
+        .collect();
+    // The sort is stable, so the name kept for each key is the first one listed
+    names.sort_by(|a, b| a.0.cmp(&b.0));
+    names.dedup_by(|a, b| a.0 == b.0);
+
+    for (_, name) in names {
+        let mut path = prefix.to_vec();
+        path.push(name.to_string());
+        paths.push(path.clone());
+
+        union_items(
+            children(here, name, case_insensitive),
+            children(there, name, case_insensitive),
+            &path,
+            case_insensitive,
+            paths,
+        );
+    }
+}
+
+/// Finds the paths ignored on only one side, skipping the contents of a path
+/// already reported. `paths` must list parents before their contents.
+pub(crate) fn ignore_mismatches(
+    paths: &[Vec<String>],
+    ignored_here: impl Fn(&[String]) -> bool,
+    ignored_there: impl Fn(&[String]) -> bool,
+) -> Vec<IgnoreMismatch> {
+    let mut mismatches: Vec<IgnoreMismatch> = vec![];
+
+    for path in paths {
+        if mismatches
+            .last()
+            .is_some_and(|reported| path.starts_with(&reported.folder_path))
+        {
+            continue;
+        }
+
+        let (here, there) = (ignored_here(path), ignored_there(path));
+        if here != there {
+            mismatches.push(IgnoreMismatch {
+                folder_path: path.clone(),
+                ignored_here: here,
+                ignored_there: there,
+            });
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, kind: ItemKind, size_kb: u64, items: Vec<ItemGroup>) -> ItemGroup {
+        serde_json::from_value(serde_json::json!({
+            "id": name,
+            "name": name,
+            "kind": kind,
+            "size_kb": size_kb,
+            "items": items,
+            "leaf": false,
+        }))
+        .unwrap()
+    }
+
+    fn path(components: &[&str]) -> Vec<String> {
+        components.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn compare_reports_topmost_differences() {
+        let episode = |name: &str, size_kb| item(name, ItemKind::File, size_kb, vec![]);
+        let here = vec![
+            item(
+                "Show B",
+                ItemKind::Dir,
+                30,
+                vec![
+                    item("Season 1", ItemKind::Dir, 20, vec![episode("E01.mkv", 20)]),
+                    item("Season 2", ItemKind::Dir, 10, vec![episode("E01.mkv", 10)]),
+                ],
+            ),
+            item("Show C", ItemKind::File, 5, vec![]),
+        ];
+        let there = vec![
+            item(
+                "Show B",
+                ItemKind::Dir,
+                25,
+                vec![item(
+                    "Season 1",
+                    ItemKind::Dir,
+                    25,
+                    vec![episode("E01.mkv", 25)],
+                )],
+            ),
+            item("Show C", ItemKind::Dir, 5, vec![]),
+            item("Show A", ItemKind::Dir, 40, vec![]),
+        ];
+
+        let diff = compare(&here, &there, false);
+
+        let only_here: Vec<_> = diff.only_here.iter().map(|i| &i.folder_path).collect();
+        assert_eq!(
+            only_here,
+            vec![&path(&["Show B", "Season 2"]), &path(&["Show C"])]
+        );
+        let only_there: Vec<_> = diff.only_there.iter().map(|i| &i.folder_path).collect();
+        assert_eq!(only_there, vec![&path(&["Show C"]), &path(&["Show A"])]);
+
+        // Only the file is reported, not the directories holding it
+        assert_eq!(diff.size_mismatches.len(), 1);
+        assert_eq!(
+            diff.size_mismatches[0].folder_path,
+            path(&["Show B", "Season 1", "E01.mkv"])
+        );
+        assert_eq!(diff.size_mismatches[0].here_size_kb, 20);
+        assert_eq!(diff.size_mismatches[0].there_size_kb, 25);
+    }
+
+    #[test]
+    fn ignore_mismatches_skip_reported_contents() {
+        let here = vec![item(
+            "Show A",
+            ItemKind::Dir,
+            0,
+            vec![item("Season 1", ItemKind::Dir, 0, vec![])],
+        )];
+        let there = vec![item("Show B", ItemKind::Dir, 0, vec![])];
+        let paths = union_paths(&here, &there, false);
+        assert_eq!(
+            paths,
+            vec![
+                path(&["Show A"]),
+                path(&["Show A", "Season 1"]),
+                path(&["Show B"])
+            ]
+        );
+
+        let mismatches = ignore_mismatches(
+            &paths,
+            |p| p[0] == "Show A" || p[0] == "Show B",
+            |p| p[0] == "Show B",
+        );
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].folder_path, path(&["Show A"]));
+        assert!(mismatches[0].ignored_here);
+        assert!(!mismatches[0].ignored_there);
+    }
+
+    #[test]
+    fn names_match_across_normalization_and_case() {
+        let file = |name: &str| item(name, ItemKind::File, 10, vec![]);
+        // "Amélie" spelled precomposed here and decomposed there
+        let here = vec![item(
+            "Am\u{e9}lie",
+            ItemKind::Dir,
+            10,
+            vec![file("Movie.mkv")],
+        )];
+        let there = vec![item(
+            "Ame\u{301}lie",
+            ItemKind::Dir,
+            10,
+            vec![file("movie.mkv")],
+        )];
+
+        // Case still matters unless the category ignores it
+        let diff = compare(&here, &there, false);
+        assert_eq!(
+            diff.only_here[0].folder_path,
+            path(&["Am\u{e9}lie", "Movie.mkv"])
+        );
+        assert_eq!(
+            diff.only_there[0].folder_path,
+            path(&["Am\u{e9}lie", "movie.mkv"])
+        );
+        let diff = compare(&here, &there, true);
+        assert!(diff.only_here.is_empty() && diff.only_there.is_empty());
+
+        assert_eq!(
+            union_paths(&here, &there, true),
+            vec![path(&["Am\u{e9}lie"]), path(&["Am\u{e9}lie", "Movie.mkv"])]
+        );
+        assert_eq!(
+            path_key(&path(&["Ame\u{301}lie", "MOVIE.mkv"]), true),
+            path_key(&path(&["Am\u{e9}lie", "movie.mkv"]), true)
+        );
+    }
+}
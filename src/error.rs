@@ -0,0 +1,142 @@
+use crate::filesystem::ScanError;
+use crate::ignores::IgnoreError;
+use crate::remote::RemoteError;
+use crate::syncthing::SyncthingError;
+use axum::{
+    Json,
+    extract::{FromRequest, Request, rejection::JsonRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+/// Stable, machine-readable reason for a failed request. Each code always maps to
+/// the same HTTP status.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Unauthorized,
+    InvalidRequest,
+    CategoryNotFound,
+    ItemNotFound,
+    EmptyPath,
+    /// A path component such as ".." or one containing a separator
+    PathOutsideCategory,
+    InvalidRawName,
+    /// Deleting the path would make Syncthing delete it on other devices
+    NotIgnored,
+    NotReceiveOnly,
+    IoError,
+    SyncthingNotConfigured,
+    SyncthingError,
+    RemoteAgentError,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::InvalidRequest
+            | ErrorCode::EmptyPath
+            | ErrorCode::PathOutsideCategory
+            | ErrorCode::InvalidRawName => StatusCode::BAD_REQUEST,
+            ErrorCode::CategoryNotFound | ErrorCode::ItemNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::NotIgnored | ErrorCode::NotReceiveOnly => StatusCode::CONFLICT,
+            ErrorCode::IoError | ErrorCode::SyncthingNotConfigured | ErrorCode::Internal => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            ErrorCode::SyncthingError | ErrorCode::RemoteAgentError => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+/// The body of every error response
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// The path and I/O error kind behind an `io_error` from reading the filesystem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ScanError>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> ApiError {
+        ApiError {
+            code,
+            message: message.into(),
+            error: None,
+        }
+    }
+
+    pub fn category_not_found(category_id: &str) -> ApiError {
+        ApiError::new(
+            ErrorCode::CategoryNotFound,
+            format!("Category ID '{}' not found", category_id),
+        )
+    }
+
+    pub fn empty_path() -> ApiError {
+        ApiError::new(ErrorCode::EmptyPath, "Folder path cannot be empty")
+    }
+
+    /// A category directory or item that could not be read
+    pub fn scan(category_name: &str, error: ScanError) -> ApiError {
+        ApiError {
+            code: ErrorCode::IoError,
+            message: format!("Unable to read category '{}'", category_name),
+            error: Some(error),
+        }
+    }
+}
+
+impl From<IgnoreError> for ApiError {
+    fn from(err: IgnoreError) -> ApiError {
+        let code = match err {
+            IgnoreError::NotConfigured => ErrorCode::SyncthingNotConfigured,
+            IgnoreError::Syncthing(_) => ErrorCode::SyncthingError,
+        };
+        ApiError::new(code, format!("Unable to access ignores: {}", err))
+    }
+}
+
+impl From<SyncthingError> for ApiError {
+    fn from(err: SyncthingError) -> ApiError {
+        ApiError::new(ErrorCode::SyncthingError, err.to_string())
+    }
+}
+
+impl From<RemoteError> for ApiError {
+    fn from(err: RemoteError) -> ApiError {
+        ApiError::new(ErrorCode::RemoteAgentError, err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.code.status(), Json(self)).into_response()
+    }
+}
+
+/// A JSON request body. Unlike `Json`, bodies that are malformed or missing fields are
+/// rejected with an `invalid_request` error rather than plain text.
+pub(crate) struct ApiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) => Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                rejection.body_text(),
+            )),
+        }
+    }
+}
@@ -1,119 +1,910 @@
+use crate::config::SymlinkPolicy;
+use crate::error::ErrorCode;
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, percent_encode};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
 
 /* generic functions - keeping for backward compatibility if needed */
 
 /// Checks if a directory entry represents a Syncthing system file or folder
 /// These include .stignore, .stfolder, .stversions, and any other .st* items
 fn is_syncthing_system_item(entry: &fs::DirEntry) -> bool {
-    entry.file_name().to_string_lossy().starts_with(".st")
+    is_syncthing_system_name(&entry.file_name())
 }
 
-/// Helper function to convert folder path components to a full filesystem path
-fn build_full_path(base_path: &Path, folder_path_components: &[String]) -> PathBuf {
-    let mut full_path = base_path.to_path_buf();
-    for component in folder_path_components {
-        full_path = full_path.join(component);
-    }
-    full_path
+fn is_syncthing_system_name(name: &OsStr) -> bool {
+    name.to_string_lossy().starts_with(".st")
+}
+
+/// Checks that a requested path component names a single entry, rejecting `..`, `/` and the like
+pub(crate) fn is_plain_name(name: &OsStr) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
 }
 
 /// Helper function to convert folder path components to Unix-style string for .stignore
-fn build_unix_path_string(folder_path_components: &[String]) -> String {
+pub(crate) fn build_unix_path_string<S: AsRef<OsStr>>(folder_path_components: &[S]) -> String {
     if folder_path_components.is_empty() {
         "/".to_string()
     } else {
-        format!("/{}", folder_path_components.join("/"))
+        let components: Vec<_> = folder_path_components
+            .iter()
+            .map(|component| component.as_ref().to_string_lossy())
+            .collect();
+        format!("/{}", components.join("/"))
+    }
+}
+
+/// Reduces a name to the form used for comparisons, so that NFC and NFD spellings
+/// (and optionally different cases) of the same name are equal
+pub(crate) fn match_key(name: &str, case_insensitive: bool) -> String {
+    let normalized: String = name.nfc().collect();
+    match case_insensitive {
+        true => normalized.to_lowercase(),
+        false => normalized,
+    }
+}
+
+/// Checks if two filenames refer to the same item, ignoring Unicode normalization differences
+pub(crate) fn names_match(a: &OsStr, b: &OsStr, case_insensitive: bool) -> bool {
+    if a == b {
+        return true;
+    }
+
+    match (a.to_str(), b.to_str()) {
+        (Some(a), Some(b)) => match_key(a, case_insensitive) == match_key(b, case_insensitive),
+        _ => false,
+    }
+}
+
+/// Resolves folder path components to the path that exists on disk, matching each
+/// component exactly first and falling back to a normalization-aware directory lookup.
+/// Components that aren't plain names never resolve, so paths can't escape the base path.
+/// Neither do Syncthing system items, nor paths through a symlinked directory unless
+/// symlinks are followed.
+pub fn resolve_path<S: AsRef<OsStr>>(
+    base_path: &Path,
+    folder_path_components: &[S],
+    symlinks: SymlinkPolicy,
+    case_insensitive: bool,
+) -> Result<Option<PathBuf>, ScanError> {
+    let mut resolved = base_path.to_path_buf();
+
+    for (index, component) in folder_path_components.iter().enumerate() {
+        let component = component.as_ref();
+        if !is_plain_name(component) || is_syncthing_system_name(component) {
+            return Ok(None);
+        }
+
+        // Only the last component may be a symlink, which is then the item itself
+        if index > 0 && symlinks != SymlinkPolicy::Follow {
+            let is_symlink = resolved
+                .symlink_metadata()
+                .is_ok_and(|metadata| metadata.file_type().is_symlink());
+            if is_symlink {
+                return Ok(None);
+            }
+        }
+
+        let exact = resolved.join(component);
+        if exact.symlink_metadata().is_ok() {
+            resolved = exact;
+            continue;
+        }
+
+        let entries = match fs::read_dir(&resolved) {
+            Ok(entries) => entries,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+                ) =>
+            {
+                return Ok(None);
+            }
+            Err(err) => return Err(ScanError::new(&resolved, &err).relative_to(base_path)),
+        };
+
+        match entries
+            .filter_map(|entry| entry.ok())
+            .find(|entry| names_match(&entry.file_name(), component, case_insensitive))
+        {
+            Some(entry) => resolved = entry.path(),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(resolved))
+}
+
+/// Checks if a .stignore line matches the normalized path. Lines starting with
+/// `(?i)` match case-insensitively, as they do in Syncthing.
+pub(crate) fn ignore_line_matches(
+    line: &str,
+    normalized_path: &str,
+    case_insensitive: bool,
+) -> bool {
+    let line = line.trim();
+    let (pattern, case_insensitive) = match line.strip_prefix("(?i)") {
+        Some(pattern) => (pattern, true),
+        None => (line, case_insensitive),
+    };
+
+    pattern == normalized_path
+        || match_key(pattern, case_insensitive) == match_key(normalized_path, case_insensitive)
+}
+
+/// Bytes escaped in raw names, on top of every non-ASCII byte
+const RAW_NAME_ESCAPES: &AsciiSet = &CONTROLS.add(b'%');
+
+/// Percent-encodes a filename that isn't valid UTF-8, so clients can address it losslessly
+fn encode_raw_name(name: &OsStr) -> Option<String> {
+    match name.to_str() {
+        Some(_) => None,
+        None => Some(percent_encode(name.as_encoded_bytes(), RAW_NAME_ESCAPES).to_string()),
+    }
+}
+
+#[cfg(unix)]
+fn decode_raw_name(raw_name: &str) -> Option<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(OsString::from_vec(percent_decode_str(raw_name).collect()))
+}
+
+#[cfg(not(unix))]
+fn decode_raw_name(raw_name: &str) -> Option<OsString> {
+    percent_decode_str(raw_name)
+        .decode_utf8()
+        .ok()
+        .map(|name| OsString::from(name.as_ref()))
+}
+
+/// Converts requested path components into filesystem names. Components whose index is
+/// in `raw_components` are percent-encoded `raw_name`s, the others are used as-is.
+/// Returns `None` if a raw component can't be represented on this platform, or an index
+/// doesn't refer to a component.
+pub fn decode_path_components(
+    components: &[String],
+    raw_components: &[usize],
+) -> Option<Vec<OsString>> {
+    if raw_components
+        .iter()
+        .any(|index| *index >= components.len())
+    {
+        return None;
     }
+
+    components
+        .iter()
+        .enumerate()
+        .map(|(index, component)| match raw_components.contains(&index) {
+            true => decode_raw_name(component),
+            false => Some(OsString::from(component)),
+        })
+        .collect()
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Whether an item is a plain file, a directory that contains further items,
+/// or a symlink that was reported without being followed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ItemKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub(crate) struct ItemGroup {
     pub id: String,
     pub name: String,
+    /// Percent-encoded filename bytes, only set when the name isn't valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<String>,
+    pub kind: ItemKind,
+    /// Apparent size, counting every hard link to a file separately
     pub size_kb: u64,
+    /// Size with each hard-linked file counted once
+    #[serde(default)]
+    pub unique_size_kb: u64,
+    /// Space freed by deleting this item, excluding files still linked from elsewhere
+    #[serde(default)]
+    pub reclaimable_size_kb: u64,
     pub items: Vec<ItemGroup>,
+    /// True for directories that contain no subdirectories
     pub leaf: bool,
+    /// Last modification time, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    /// Newest modification time of any file within this item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newest_modified: Option<u64>,
+    /// Number of files within this item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_count: Option<u64>,
+    /// File extension, only set for files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    /// Permission bits in octal (e.g. "644"), only set for files on Unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+    /// Target of the symlink this item was reached through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+    /// True for symlinks whose target does not exist
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub broken_link: bool,
+    /// Entries within this directory that could not be read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ScanError>,
+    /// Hard-linked files seen within this item, consumed when grouped into a parent
+    #[serde(skip)]
+    hard_links: HardLinks,
 }
 
-fn dir_to_item(entry: fs::DirEntry) -> ItemGroup {
-    let filename = entry.file_name().to_string_lossy().to_string();
-    let entry_path = entry.path();
+impl ItemGroup {
+    /// Rewrites the paths of every error within this item relative to the category directory
+    fn relativize_errors(&mut self, base_path: &Path) {
+        for error in self.errors.iter_mut() {
+            *error = error.clone().relative_to(base_path);
+        }
+        for child in self.items.iter_mut() {
+            child.relativize_errors(base_path);
+        }
+    }
+}
+
+/// A path that could not be read while scanning, with the kind of I/O error encountered
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct ScanError {
+    pub path: String,
+    pub kind: String,
+}
+
+impl ScanError {
+    fn new(path: &Path, err: &std::io::Error) -> ScanError {
+        tracing::warn!("Unable to read path {:?}: {:?}", path, err.kind());
+        ScanError {
+            path: path.to_string_lossy().to_string(),
+            kind: format!("{:?}", err.kind()),
+        }
+    }
+
+    /// Rewrites the path relative to the category directory, in the same form as
+    /// .stignore paths, so API clients never see where the server keeps its files
+    fn relative_to(mut self, base_path: &Path) -> ScanError {
+        let base_path = base_path.to_string_lossy();
+        let path = Path::new(&self.path);
+        let components: Vec<&OsStr> = match path.strip_prefix(base_path.as_ref()) {
+            Ok(relative) => relative.iter().collect(),
+            // Never expected, but only the name is safe to report
+            Err(_) => path.file_name().into_iter().collect(),
+        };
+        self.path = build_unix_path_string(&components);
+        self
+    }
+}
+
+/// A file with more than one hard link, as seen during a single walk
+#[derive(Debug, Clone, PartialEq)]
+struct LinkedFile {
+    size_kb: u64,
+    nlink: u64,
+    seen: u64,
+}
+
+/// Hard-linked files keyed by `(dev, inode)`, so each is only counted once per walk
+#[derive(Debug, Clone, PartialEq, Default)]
+struct HardLinks(HashMap<(u64, u64), LinkedFile>);
+
+impl HardLinks {
+    #[cfg(unix)]
+    fn from_metadata(metadata: &fs::Metadata) -> HardLinks {
+        use std::os::unix::fs::MetadataExt;
+
+        let mut links = HardLinks::default();
+        if metadata.nlink() > 1 {
+            links.0.insert(
+                (metadata.dev(), metadata.ino()),
+                LinkedFile {
+                    size_kb: metadata.len() / 1024,
+                    nlink: metadata.nlink(),
+                    seen: 1,
+                },
+            );
+        }
+        links
+    }
+
+    #[cfg(not(unix))]
+    fn from_metadata(_metadata: &fs::Metadata) -> HardLinks {
+        HardLinks::default()
+    }
+
+    fn merge(&mut self, other: HardLinks) {
+        for (key, file) in other.0 {
+            self.0
+                .entry(key)
+                .and_modify(|existing| existing.seen += file.seen)
+                .or_insert(file);
+        }
+    }
 
-    let mut children = build_items(&entry_path, false);
-    let mut leaf = false;
+    fn unique_kb(&self) -> u64 {
+        self.0.values().map(|file| file.size_kb).sum()
+    }
+
+    /// Only files whose every link was seen in this walk free space when deleted
+    fn reclaimable_kb(&self) -> u64 {
+        self.0
+            .values()
+            .filter(|file| file.seen >= file.nlink)
+            .map(|file| file.size_kb)
+            .sum()
+    }
+}
 
-    if children.is_empty() {
-        children = build_items(&entry_path, true);
-        leaf = true;
+/// Builds a directory-like item from its children, aggregating size, file count and newest modification time
+pub fn group_items(id: String, name: String, mut items: Vec<ItemGroup>) -> ItemGroup {
+    // Sizes of files with a single link add up directly, hard-linked files are deduplicated
+    let mut hard_links = HardLinks::default();
+    let mut unlinked_kb = 0;
+    for child in items.iter_mut() {
+        let child_links = std::mem::take(&mut child.hard_links);
+        unlinked_kb += child.unique_size_kb - child_links.unique_kb();
+        hard_links.merge(child_links);
     }
 
     ItemGroup {
-        id: filename.clone(),
-        name: filename,
-        size_kb: children.iter().map(|c| c.size_kb).sum(),
-        items: children,
-        leaf,
+        id,
+        name,
+        raw_name: None,
+        kind: ItemKind::Dir,
+        size_kb: items.iter().map(|c| c.size_kb).sum(),
+        unique_size_kb: unlinked_kb + hard_links.unique_kb(),
+        reclaimable_size_kb: unlinked_kb + hard_links.reclaimable_kb(),
+        leaf: false,
+        modified: None,
+        newest_modified: items.iter().filter_map(|c| c.newest_modified).max(),
+        file_count: Some(items.iter().filter_map(|c| c.file_count).sum()),
+        extension: None,
+        permissions: None,
+        link_target: None,
+        broken_link: false,
+        errors: vec![],
+        hard_links,
+        items,
+    }
+}
+
+/// Returns the modification time of the metadata in seconds since the Unix epoch
+fn modified_secs(metadata: &fs::Metadata) -> Option<u64> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+}
+
+#[cfg(unix)]
+fn permissions_string(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(format!("{:o}", metadata.permissions().mode() & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn permissions_string(_metadata: &fs::Metadata) -> Option<String> {
+    None
+}
+
+/// Space and inode usage of the filesystem holding a path
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct DiskUsage {
+    /// Device the path lives on, shared by every category on the same mount
+    pub device: Option<u64>,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    /// Free space usable by the agent, excluding blocks reserved for root
+    pub available_bytes: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+pub fn disk_usage(path: &Path) -> Result<DiskUsage, ScanError> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata =
+        fs::metadata(path).map_err(|err| ScanError::new(path, &err).relative_to(path))?;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        ScanError::new(
+            path,
+            &std::io::Error::from(std::io::ErrorKind::InvalidInput),
+        )
+        .relative_to(path)
+    })?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid NUL terminated string and stat is a writable statvfs
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(ScanError::new(path, &std::io::Error::last_os_error()).relative_to(path));
+    }
+
+    let fragment_size = stat.f_frsize as u64;
+    Ok(DiskUsage {
+        device: Some(metadata.dev()),
+        total_bytes: stat.f_blocks as u64 * fragment_size,
+        free_bytes: stat.f_bfree as u64 * fragment_size,
+        available_bytes: stat.f_bavail as u64 * fragment_size,
+        total_inodes: stat.f_files as u64,
+        free_inodes: stat.f_ffree as u64,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn disk_usage(path: &Path) -> Result<DiskUsage, ScanError> {
+    Err(
+        ScanError::new(path, &std::io::Error::from(std::io::ErrorKind::Unsupported))
+            .relative_to(path),
+    )
+}
+
+/// Targets of the symlinks followed during one scan, so that nothing is walked twice.
+/// Links into the scanned directory are never followed since it is walked anyway, and
+/// neither are links to a target overlapping one already followed, which covers loops.
+struct FollowedLinks {
+    /// Canonical path of the scanned directory, unset unless following symlinks
+    root: Option<PathBuf>,
+    targets: std::sync::Mutex<Vec<PathBuf>>,
+}
+
+impl FollowedLinks {
+    fn new(root: &Path, symlinks: SymlinkPolicy) -> FollowedLinks {
+        let root = match symlinks {
+            SymlinkPolicy::Follow => fs::canonicalize(root).ok(),
+            _ => None,
+        };
+        FollowedLinks {
+            root,
+            targets: Default::default(),
+        }
+    }
+
+    /// Records a canonical target as followed, returning false when it must not be
+    fn claim(&self, target: &Path) -> bool {
+        let Some(root) = &self.root else {
+            return false;
+        };
+        if target.starts_with(root) || root.starts_with(target) {
+            return false;
+        }
+
+        let mut targets = self
+            .targets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if targets
+            .iter()
+            .any(|other| target.starts_with(other) || other.starts_with(target))
+        {
+            return false;
+        }
+        targets.push(target.to_path_buf());
+        true
     }
 }
 
-fn file_to_item(entry: fs::DirEntry) -> ItemGroup {
-    let filename = entry.file_name().to_string_lossy().to_string();
+fn dir_to_item(
+    path: &Path,
+    name: String,
+    metadata: &fs::Metadata,
+    symlinks: SymlinkPolicy,
+    followed: &FollowedLinks,
+) -> ItemGroup {
+    let scanned = scan_dir(path, symlinks, followed);
+    let (children, errors) = scanned.unwrap_or_else(|err| (vec![], vec![err]));
+    let leaf = !children.iter().any(|child| child.kind == ItemKind::Dir);
+
+    let mut item = group_items(name.clone(), name, children);
+    item.leaf = leaf;
+    item.modified = modified_secs(metadata);
+    item.errors = errors;
+    item
+}
+
+fn file_to_item(name: String, metadata: &fs::Metadata) -> ItemGroup {
+    let modified = modified_secs(metadata);
+    let extension = Path::new(&name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string());
+
+    let size_kb = metadata.len() / 1024;
+    let hard_links = HardLinks::from_metadata(metadata);
 
     ItemGroup {
-        id: filename.clone(),
-        name: filename,
-        size_kb: entry.metadata().map(|m| m.len() / 1024).unwrap_or(0),
+        id: name.clone(),
+        name,
+        raw_name: None,
+        kind: ItemKind::File,
+        size_kb,
+        unique_size_kb: size_kb,
+        reclaimable_size_kb: size_kb - hard_links.unique_kb() + hard_links.reclaimable_kb(),
         items: vec![],
         leaf: false,
+        modified,
+        newest_modified: modified,
+        file_count: Some(1),
+        extension,
+        permissions: permissions_string(metadata),
+        link_target: None,
+        broken_link: false,
+        errors: vec![],
+        hard_links,
     }
 }
 
-pub fn build_items(item_path: &Path, leaf: bool) -> Vec<ItemGroup> {
-    match fs::read_dir(item_path) {
-        Ok(paths) => match leaf {
-            true => paths
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| !is_syncthing_system_item(entry))
-                .map(file_to_item)
-                .collect(),
-            false => paths
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| !is_syncthing_system_item(entry))
-                .filter(|entry| entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
-                .map(dir_to_item)
-                .collect(),
-        },
-        Err(why) => {
-            tracing::warn!("Unable to list path: {:?}", why.kind());
-            vec![]
+/// Builds an unfollowed symlink item, reporting its target and whether it resolves
+fn link_to_item(name: String, target: Option<String>, broken_link: bool) -> ItemGroup {
+    ItemGroup {
+        id: name.clone(),
+        name,
+        raw_name: None,
+        kind: ItemKind::Symlink,
+        size_kb: 0,
+        unique_size_kb: 0,
+        reclaimable_size_kb: 0,
+        items: vec![],
+        leaf: false,
+        modified: None,
+        newest_modified: None,
+        file_count: None,
+        extension: None,
+        permissions: None,
+        link_target: target,
+        broken_link,
+        errors: vec![],
+        hard_links: HardLinks::default(),
+    }
+}
+
+fn symlink_to_item(
+    path: &Path,
+    name: String,
+    symlinks: SymlinkPolicy,
+    followed: &FollowedLinks,
+) -> Option<ItemGroup> {
+    if symlinks == SymlinkPolicy::Skip {
+        return None;
+    }
+
+    let target = fs::read_link(path)
+        .ok()
+        .map(|target| target.to_string_lossy().to_string());
+
+    // fs::metadata follows the link, failing when the target doesn't exist
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Some(link_to_item(name, target, true)),
+    };
+
+    if symlinks == SymlinkPolicy::Report {
+        return Some(link_to_item(name, target, false));
+    }
+
+    // Listing a target that is also reached another way would count its size twice
+    let claimed = fs::canonicalize(path).is_ok_and(|canonical| followed.claim(&canonical));
+    if !claimed {
+        tracing::debug!(
+            "Not following symlink at {:?}, its target is listed already",
+            path
+        );
+        return Some(link_to_item(name, target, false));
+    }
+
+    let mut item = if metadata.is_dir() {
+        dir_to_item(path, name, &metadata, symlinks, followed)
+    } else if metadata.is_file() {
+        file_to_item(name, &metadata)
+    } else {
+        return None;
+    };
+
+    item.link_target = target;
+    Some(item)
+}
+
+/// Configures the global thread pool used to scan directories.
+/// Subdirectories are walked in parallel with work-stealing, defaulting to one thread per CPU.
+pub fn init_scanner(threads: Option<usize>) -> Result<(), rayon::ThreadPoolBuildError> {
+    let mut builder =
+        rayon::ThreadPoolBuilder::new().thread_name(|index| format!("scanner-{}", index));
+
+    if let Some(threads) = threads {
+        builder = builder.num_threads(threads);
+    }
+
+    builder.build_global()
+}
+
+/// Builds the item for a single path from its (unfollowed) metadata,
+/// returning `None` for skipped symlinks and special files
+fn path_to_item(
+    path: &Path,
+    file_name: &OsStr,
+    metadata: &fs::Metadata,
+    symlinks: SymlinkPolicy,
+    followed: &FollowedLinks,
+) -> Option<ItemGroup> {
+    let name = file_name.to_string_lossy().to_string();
+    let file_type = metadata.file_type();
+
+    let item = if file_type.is_symlink() {
+        symlink_to_item(path, name, symlinks, followed)
+    } else if file_type.is_dir() {
+        Some(dir_to_item(path, name, metadata, symlinks, followed))
+    } else if file_type.is_file() {
+        Some(file_to_item(name, metadata))
+    } else {
+        // FIFOs, sockets and device nodes are never listed
+        None
+    };
+
+    item.map(|mut item| {
+        item.raw_name = encode_raw_name(file_name);
+        item
+    })
+}
+
+fn entry_to_item(
+    entry: fs::DirEntry,
+    symlinks: SymlinkPolicy,
+    followed: &FollowedLinks,
+) -> Result<Option<ItemGroup>, ScanError> {
+    let path = entry.path();
+    let metadata = entry
+        .metadata()
+        .map_err(|err| ScanError::new(&path, &err))?;
+
+    Ok(path_to_item(
+        &path,
+        &entry.file_name(),
+        &metadata,
+        symlinks,
+        followed,
+    ))
+}
+
+/// Lists a directory, returning its items along with any entries that couldn't be read
+fn scan_dir(
+    item_path: &Path,
+    symlinks: SymlinkPolicy,
+    followed: &FollowedLinks,
+) -> Result<(Vec<ItemGroup>, Vec<ScanError>), ScanError> {
+    let paths = fs::read_dir(item_path).map_err(|err| ScanError::new(item_path, &err))?;
+
+    // Collect the listing first so the parallel map preserves directory order
+    let mut errors = vec![];
+    let mut entries = vec![];
+    for entry in paths {
+        match entry {
+            Ok(entry) if is_syncthing_system_item(&entry) => {}
+            Ok(entry) => entries.push(entry),
+            Err(err) => errors.push(ScanError::new(item_path, &err)),
         }
     }
+
+    let scanned: Vec<Result<Option<ItemGroup>, ScanError>> = entries
+        .into_par_iter()
+        .map(|entry| entry_to_item(entry, symlinks, followed))
+        .collect();
+
+    let mut items = vec![];
+    for result in scanned {
+        match result {
+            Ok(Some(item)) => items.push(item),
+            Ok(None) => {}
+            Err(err) => errors.push(err),
+        }
+    }
+
+    Ok((items, errors))
+}
+
+/// Scans a directory into an item with the given id and name, recursing into directories
+/// and handling symlinks according to the given policy.
+/// Unreadable entries are reported on the item they belong to, only failing to list
+/// the directory itself is an error.
+pub fn scan_items(
+    item_path: &Path,
+    id: String,
+    name: String,
+    symlinks: SymlinkPolicy,
+) -> Result<ItemGroup, ScanError> {
+    let followed = FollowedLinks::new(item_path, symlinks);
+    let (items, errors) =
+        scan_dir(item_path, symlinks, &followed).map_err(|err| err.relative_to(item_path))?;
+    let mut item = group_items(id, name, items);
+    item.errors = errors;
+    item.relativize_errors(item_path);
+    Ok(item)
 }
 
-pub fn get_item(start: &Path, path: &[&str]) -> Option<ItemGroup> {
+/// Finds an item by resolving the given names down from the start directory,
+/// only scanning the subtree of the requested item.
+/// Names are matched regardless of Unicode normalization, and optionally case.
+/// Fails when a directory along the way cannot be read.
+pub fn get_item(
+    start: &Path,
+    path: &[OsString],
+    symlinks: SymlinkPolicy,
+    case_insensitive: bool,
+) -> Result<Option<ItemGroup>, ScanError> {
     if path.is_empty() {
-        return None;
+        return Ok(None);
+    }
+
+    // Syncthing system items and paths through unfollowed links never resolve, as they aren't listed
+    match resolve_path(start, path, symlinks, case_insensitive)? {
+        Some(full_path) => item_at(start, &full_path, symlinks),
+        None => Ok(None),
+    }
+}
+
+/// Scans the item at a resolved path within the start directory
+fn item_at(
+    start: &Path,
+    full_path: &Path,
+    symlinks: SymlinkPolicy,
+) -> Result<Option<ItemGroup>, ScanError> {
+    let metadata = match fs::symlink_metadata(full_path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(ScanError::new(full_path, &err).relative_to(start)),
+    };
+
+    // An item reached through a followed link counts as followed, so links inside it can't loop back
+    let followed = FollowedLinks::new(start, symlinks);
+    if let Some(parent) = full_path
+        .parent()
+        .and_then(|parent| fs::canonicalize(parent).ok())
+    {
+        followed.claim(&parent);
     }
 
-    let item_name = path[0];
-    let children = build_items(start, false);
-    let found = children
+    let file_name = full_path.file_name().unwrap_or_default();
+    let mut item = path_to_item(full_path, file_name, &metadata, symlinks, &followed);
+    if let Some(item) = item.as_mut() {
+        item.relativize_errors(start);
+    }
+    Ok(item)
+}
+
+/// A regular file found by `list_files`
+#[derive(Debug)]
+pub(crate) struct ListedFile {
+    pub path: PathBuf,
+    /// Path components relative to the start directory, in the form API requests use
+    pub components: Vec<String>,
+    /// Indexes of the components given as percent-encoded raw names
+    pub raw_components: Vec<usize>,
+    /// Metadata of the file, following the symlink it was reached through
+    pub metadata: fs::Metadata,
+}
+
+/// Lists the regular files within the item at the given path, or within the whole start
+/// directory when the path is empty. Files are found by the same walk as listings, so
+/// symlinks are handled by the given policy and Syncthing system items are skipped.
+/// Fails with the first entry that couldn't be read, since a partial list would go unnoticed.
+pub fn list_files(
+    start: &Path,
+    path: &[OsString],
+    symlinks: SymlinkPolicy,
+    case_insensitive: bool,
+) -> Result<Option<Vec<ListedFile>>, ScanError> {
+    let (full_path, item) = if path.is_empty() {
+        let item = scan_items(start, String::new(), String::new(), symlinks)?;
+        (start.to_path_buf(), item)
+    } else {
+        let Some(full_path) = resolve_path(start, path, symlinks, case_insensitive)? else {
+            return Ok(None);
+        };
+        match item_at(start, &full_path, symlinks)? {
+            Some(item) => (full_path, item),
+            None => return Ok(None),
+        }
+    };
+
+    let mut components = vec![];
+    let mut raw_components = vec![];
+    for (index, name) in full_path
+        .strip_prefix(start)
+        .unwrap_or(Path::new(""))
         .iter()
-        .find(|child| child.name == item_name)
-        .map(|c| c.to_owned());
-
-    match path.len() {
-        1 => found,
-        _ => match found {
-            Some(child) => {
-                let start_here = start.join(&child.name);
-                get_item(start_here.as_path(), &path[1..])
+        .enumerate()
+    {
+        match encode_raw_name(name) {
+            Some(raw_name) => {
+                raw_components.push(index);
+                components.push(raw_name);
             }
-            None => None,
-        },
+            None => components.push(name.to_string_lossy().to_string()),
+        }
+    }
+
+    let mut files = vec![];
+    collect_listed_files(
+        start,
+        &full_path,
+        &item,
+        components,
+        raw_components,
+        &mut files,
+    )?;
+    Ok(Some(files))
+}
+
+fn collect_listed_files(
+    start: &Path,
+    path: &Path,
+    item: &ItemGroup,
+    components: Vec<String>,
+    raw_components: Vec<usize>,
+    files: &mut Vec<ListedFile>,
+) -> Result<(), ScanError> {
+    if let Some(error) = item.errors.first() {
+        return Err(error.clone());
     }
+
+    match item.kind {
+        ItemKind::File => {
+            let metadata =
+                fs::metadata(path).map_err(|err| ScanError::new(path, &err).relative_to(start))?;
+            files.push(ListedFile {
+                path: path.to_path_buf(),
+                components,
+                raw_components,
+                metadata,
+            });
+        }
+        ItemKind::Dir => {
+            for child in &item.items {
+                let name = child
+                    .raw_name
+                    .as_deref()
+                    .and_then(decode_raw_name)
+                    .unwrap_or_else(|| OsString::from(&child.name));
+
+                let mut child_raw_components = raw_components.clone();
+                if child.raw_name.is_some() {
+                    child_raw_components.push(components.len());
+                }
+                let mut child_components = components.clone();
+                child_components.push(child.raw_name.clone().unwrap_or_else(|| child.name.clone()));
+
+                collect_listed_files(
+                    start,
+                    &path.join(name),
+                    child,
+                    child_components,
+                    child_raw_components,
+                    files,
+                )?;
+            }
+        }
+        // Unfollowed symlinks have no contents of their own
+        ItemKind::Symlink => {}
+    }
+    Ok(())
 }
 
 /// Result of adding a path to .stignore file
@@ -127,6 +918,8 @@ pub enum StignoreResult {
         ignored_path: String,
     },
     Error {
+        /// `io_error` for a failed .stignore write, `syncthing_error` when Syncthing failed
+        code: ErrorCode,
         message: String,
     },
 }
@@ -153,18 +946,25 @@ pub enum DeleteResult {
 /// * `category_base_path` - The base directory of the category (e.g., "/home/user/media/movies")
 /// * `folder_path_components` - The folder path as components (e.g., ["Movie Name (2023)"])
 ///
+/// * `case_insensitive` - Whether to match rules regardless of case
+///
 /// # Returns
 /// * `bool` - True if the folder path is ignored, false otherwise
 pub fn is_path_ignored(
     category_base_path: &std::path::Path,
     folder_path_components: &[String],
+    case_insensitive: bool,
 ) -> bool {
     let folder_path_str = build_unix_path_string(folder_path_components);
-    is_path_ignored_str(category_base_path, &folder_path_str)
+    is_path_ignored_str(category_base_path, &folder_path_str, case_insensitive)
 }
 
 /// Internal helper that works with path strings
-fn is_path_ignored_str(category_base_path: &std::path::Path, folder_path: &str) -> bool {
+fn is_path_ignored_str(
+    category_base_path: &std::path::Path,
+    folder_path: &str,
+    case_insensitive: bool,
+) -> bool {
     let stignore_path = category_base_path.join(".stignore");
 
     // Normalize the path to ensure consistency
@@ -183,7 +983,7 @@ fn is_path_ignored_str(category_base_path: &std::path::Path, folder_path: &str)
     // Check if the path is in the ignore list
     ignore_content
         .lines()
-        .any(|line| line.trim() == normalized_path)
+        .any(|line| ignore_line_matches(line, &normalized_path, case_insensitive))
 }
 
 /// Adds a folder path to the .stignore file in the specified category directory.
@@ -193,6 +993,8 @@ fn is_path_ignored_str(category_base_path: &std::path::Path, folder_path: &str)
 /// * `category_base_path` - The base directory of the category (e.g., "/home/user/media/movies")
 /// * `folder_path_components` - The folder path as components (e.g., ["Movie Name (2023)"])
 /// * `category_name` - Name of the category for success messages
+/// * `case_insensitive` - Whether to match existing rules regardless of case, new rules are
+///   written with Syncthing's `(?i)` prefix
 ///
 /// # Returns
 /// * `StignoreResult` - Success, already ignored, or error result
@@ -200,9 +1002,15 @@ pub fn add_to_stignore(
     category_base_path: &std::path::Path,
     folder_path_components: &[String],
     category_name: &str,
+    case_insensitive: bool,
 ) -> StignoreResult {
     let folder_path_str = build_unix_path_string(folder_path_components);
-    add_to_stignore_str(category_base_path, &folder_path_str, category_name)
+    add_to_stignore_str(
+        category_base_path,
+        &folder_path_str,
+        category_name,
+        case_insensitive,
+    )
 }
 
 /// Internal helper that works with path strings
@@ -210,6 +1018,7 @@ fn add_to_stignore_str(
     category_base_path: &std::path::Path,
     folder_path: &str,
     category_name: &str,
+    case_insensitive: bool,
 ) -> StignoreResult {
     let stignore_path = category_base_path.join(".stignore");
 
@@ -226,7 +1035,7 @@ fn add_to_stignore_str(
     // Check if the path is already ignored
     if ignore_content
         .lines()
-        .any(|line| line.trim() == normalized_path)
+        .any(|line| ignore_line_matches(line, &normalized_path, case_insensitive))
     {
         return StignoreResult::AlreadyIgnored {
             ignored_path: normalized_path,
@@ -237,6 +1046,9 @@ fn add_to_stignore_str(
     if !ignore_content.is_empty() && !ignore_content.ends_with('\n') {
         ignore_content.push('\n');
     }
+    if case_insensitive {
+        ignore_content.push_str("(?i)");
+    }
     ignore_content.push_str(&normalized_path);
     ignore_content.push('\n');
 
@@ -250,6 +1062,7 @@ fn add_to_stignore_str(
             ),
         },
         Err(err) => StignoreResult::Error {
+            code: ErrorCode::IoError,
             message: format!("Failed to write .stignore file: {}", err),
         },
     }
@@ -262,23 +1075,42 @@ fn add_to_stignore_str(
 /// * `category_base_path` - The base directory of the category (e.g., "/home/user/media/movies")
 /// * `folder_path_components` - The folder path as components (e.g., ["Movie Name (2023)"])
 /// * `category_name` - Name of the category for success messages
+/// * `symlinks` - Whether the path may lead through symlinked directories
+/// * `case_insensitive` - Whether to match path components regardless of case
 ///
 /// # Returns
 /// * `DeleteResult` - Success, not found, or error result
 pub fn delete_from_filesystem(
     category_base_path: &std::path::Path,
-    folder_path_components: &[String],
+    folder_path_components: &[OsString],
     category_name: &str,
+    symlinks: SymlinkPolicy,
+    case_insensitive: bool,
 ) -> DeleteResult {
-    let full_path = build_full_path(category_base_path, folder_path_components);
     let normalized_folder_path = build_unix_path_string(folder_path_components);
 
-    // Check if the path exists
-    if !full_path.exists() {
-        return DeleteResult::NotFound {
-            requested_path: normalized_folder_path,
-        };
-    }
+    // Find the path as it exists on disk, which may be spelled differently
+    let full_path = match resolve_path(
+        category_base_path,
+        folder_path_components,
+        symlinks,
+        case_insensitive,
+    ) {
+        Ok(Some(full_path)) if full_path.exists() => full_path,
+        Ok(_) => {
+            return DeleteResult::NotFound {
+                requested_path: normalized_folder_path,
+            };
+        }
+        Err(err) => {
+            return DeleteResult::Error {
+                message: format!(
+                    "Failed to resolve '{}': {}",
+                    normalized_folder_path, err.kind
+                ),
+            };
+        }
+    };
 
     // Attempt to delete the path
     let result = if full_path.is_dir() {
@@ -300,3 +1132,384 @@ pub fn delete_from_filesystem(
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_library(temp_dir: &TempDir) {
+        for show in 0..4 {
+            for season in 0..3 {
+                let season_dir = temp_dir
+                    .path()
+                    .join(format!("Show {}", show))
+                    .join(format!("Season {}", season));
+                fs::create_dir_all(&season_dir).unwrap();
+
+                for episode in 0..5 {
+                    let content = vec![0u8; 1024 * (show + season + episode + 1)];
+                    fs::write(season_dir.join(format!("E{:02}.mkv", episode)), content).unwrap();
+                }
+            }
+        }
+        fs::write(temp_dir.path().join(".stignore"), "").unwrap();
+    }
+
+    fn build_items(path: &Path, symlinks: SymlinkPolicy) -> Vec<ItemGroup> {
+        scan_items(path, "root".to_string(), "Root".to_string(), symlinks)
+            .unwrap()
+            .items
+    }
+
+    fn scan_with_threads(path: &Path, threads: usize) -> Vec<ItemGroup> {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| build_items(path, SymlinkPolicy::Report))
+    }
+
+    #[test]
+    fn parallel_scan_matches_serial_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        create_library(&temp_dir);
+
+        let serial = scan_with_threads(temp_dir.path(), 1);
+        let parallel = scan_with_threads(temp_dir.path(), 4);
+
+        assert_eq!(serial.len(), 4);
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn scan_reports_file_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        create_library(&temp_dir);
+
+        let items = build_items(temp_dir.path(), SymlinkPolicy::Report);
+        let show = items.iter().find(|item| item.name == "Show 0").unwrap();
+        assert_eq!(show.file_count, Some(15));
+        assert!(show.modified.is_some());
+        assert!(show.newest_modified.is_some());
+        assert!(show.extension.is_none());
+
+        let season = show
+            .items
+            .iter()
+            .find(|item| item.name == "Season 0")
+            .unwrap();
+        assert!(season.leaf);
+        assert_eq!(season.file_count, Some(5));
+
+        let episode = season
+            .items
+            .iter()
+            .find(|item| item.name == "E00.mkv")
+            .unwrap();
+        assert_eq!(episode.file_count, Some(1));
+        assert_eq!(episode.extension.as_deref(), Some("mkv"));
+        assert_eq!(episode.modified, episode.newest_modified);
+        assert!(episode.newest_modified <= season.newest_modified);
+        #[cfg(unix)]
+        assert!(episode.permissions.is_some());
+    }
+
+    #[test]
+    fn scan_lists_files_alongside_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let movie_dir = temp_dir.path().join("Movie (2023)");
+        fs::create_dir_all(movie_dir.join("Subs")).unwrap();
+        fs::write(movie_dir.join("Subs").join("en.srt"), vec![0u8; 2048]).unwrap();
+        fs::write(movie_dir.join("Movie (2023).mkv"), vec![0u8; 8192]).unwrap();
+
+        let items = build_items(temp_dir.path(), SymlinkPolicy::Report);
+        assert_eq!(items.len(), 1);
+
+        let movie = &items[0];
+        assert_eq!(movie.kind, ItemKind::Dir);
+        assert!(!movie.leaf);
+        assert_eq!(movie.size_kb, 10);
+        assert_eq!(movie.file_count, Some(2));
+
+        let video = movie
+            .items
+            .iter()
+            .find(|item| item.name == "Movie (2023).mkv")
+            .unwrap();
+        assert_eq!(video.kind, ItemKind::File);
+        assert_eq!(video.size_kb, 8);
+
+        let subs = movie.items.iter().find(|item| item.name == "Subs").unwrap();
+        assert_eq!(subs.kind, ItemKind::Dir);
+        assert!(subs.leaf);
+    }
+
+    #[cfg(unix)]
+    fn create_linked_library(temp_dir: &TempDir) {
+        let show_dir = temp_dir.path().join("Show").join("Season 1");
+        fs::create_dir_all(&show_dir).unwrap();
+        fs::write(show_dir.join("E01.mkv"), vec![0u8; 4096]).unwrap();
+
+        // A loop back to the category root, a link to a sibling and a dangling link
+        std::os::unix::fs::symlink(temp_dir.path(), show_dir.join("loop")).unwrap();
+        std::os::unix::fs::symlink(temp_dir.path().join("Show"), temp_dir.path().join("Alias"))
+            .unwrap();
+        std::os::unix::fs::symlink(
+            temp_dir.path().join("missing"),
+            temp_dir.path().join("broken"),
+        )
+        .unwrap();
+
+        // Sockets are special files that should never be listed
+        std::os::unix::net::UnixListener::bind(temp_dir.path().join("socket")).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_skips_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        create_linked_library(&temp_dir);
+
+        let items = build_items(temp_dir.path(), SymlinkPolicy::Skip);
+        let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["Show"]);
+        assert_eq!(items[0].size_kb, 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_reports_symlinks_without_following() {
+        let temp_dir = TempDir::new().unwrap();
+        create_linked_library(&temp_dir);
+
+        let items = build_items(temp_dir.path(), SymlinkPolicy::Report);
+        assert_eq!(items.len(), 3);
+        assert!(!items.iter().any(|item| item.name == "socket"));
+
+        let alias = items.iter().find(|item| item.name == "Alias").unwrap();
+        assert_eq!(alias.kind, ItemKind::Symlink);
+        assert!(alias.items.is_empty());
+        assert!(!alias.broken_link);
+
+        let broken = items.iter().find(|item| item.name == "broken").unwrap();
+        assert_eq!(broken.kind, ItemKind::Symlink);
+        assert!(broken.broken_link);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_follows_symlinks_without_looping() {
+        let temp_dir = TempDir::new().unwrap();
+        create_linked_library(&temp_dir);
+
+        let items = build_items(temp_dir.path(), SymlinkPolicy::Follow);
+        assert_eq!(items.len(), 3);
+
+        // Show is listed already, so its alias isn't walked and counted again
+        let alias = items.iter().find(|item| item.name == "Alias").unwrap();
+        assert_eq!(alias.kind, ItemKind::Symlink);
+        assert_eq!(alias.size_kb, 0);
+        assert!(alias.link_target.is_some());
+        let total: u64 = items.iter().map(|item| item.size_kb).sum();
+        assert_eq!(total, 4);
+
+        let show = items.iter().find(|item| item.name == "Show").unwrap();
+        let looped = show.items[0]
+            .items
+            .iter()
+            .find(|item| item.name == "loop")
+            .unwrap();
+        assert_eq!(looped.kind, ItemKind::Symlink);
+        assert!(looped.items.is_empty());
+
+        let broken = items.iter().find(|item| item.name == "broken").unwrap();
+        assert!(broken.broken_link);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_follows_each_outside_target_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let category = temp_dir.path().join("category");
+        let external = temp_dir.path().join("external");
+        fs::create_dir_all(&category).unwrap();
+        fs::create_dir_all(external.join("Season 1")).unwrap();
+        fs::write(external.join("Season 1").join("E01.mkv"), vec![0u8; 4096]).unwrap();
+
+        // Two links to the same directory outside the category, and one back out of it
+        std::os::unix::fs::symlink(&external, category.join("Show")).unwrap();
+        std::os::unix::fs::symlink(&external, category.join("Show Again")).unwrap();
+        std::os::unix::fs::symlink(&external, external.join("Season 1").join("up")).unwrap();
+
+        let items = build_items(&category, SymlinkPolicy::Follow);
+        let followed: Vec<&ItemGroup> = items
+            .iter()
+            .filter(|item| item.kind == ItemKind::Dir)
+            .collect();
+        assert_eq!(followed.len(), 1);
+        assert_eq!(followed[0].size_kb, 4);
+        assert!(followed[0].link_target.is_some());
+
+        let total: u64 = items.iter().map(|item| item.size_kb).sum();
+        assert_eq!(total, 4);
+        let up = followed[0].items[0]
+            .items
+            .iter()
+            .find(|item| item.name == "up")
+            .unwrap();
+        assert_eq!(up.kind, ItemKind::Symlink);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_deduplicates_hard_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let downloads = temp_dir.path().join("downloads");
+        let movie = temp_dir.path().join("Movie (2023)");
+        fs::create_dir_all(&downloads).unwrap();
+        fs::create_dir_all(&movie).unwrap();
+        fs::write(downloads.join("movie.mkv"), vec![0u8; 8192]).unwrap();
+        fs::hard_link(downloads.join("movie.mkv"), movie.join("Movie (2023).mkv")).unwrap();
+        fs::write(movie.join("poster.jpg"), vec![0u8; 2048]).unwrap();
+
+        let category = scan_items(
+            temp_dir.path(),
+            "movies".to_string(),
+            "Movies".to_string(),
+            SymlinkPolicy::Report,
+        )
+        .unwrap();
+        let movie_item = category
+            .items
+            .iter()
+            .find(|item| item.name == "Movie (2023)")
+            .unwrap();
+        assert_eq!(movie_item.size_kb, 10);
+        assert_eq!(movie_item.unique_size_kb, 10);
+        // The video is still linked from downloads, so only the poster is freed
+        assert_eq!(movie_item.reclaimable_size_kb, 2);
+
+        assert_eq!(category.size_kb, 18);
+        assert_eq!(category.unique_size_kb, 10);
+        assert_eq!(category.reclaimable_size_kb, 10);
+    }
+
+    #[test]
+    fn scan_fails_for_missing_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("unmounted");
+
+        let err = scan_items(
+            &missing,
+            "root".to_string(),
+            "Root".to_string(),
+            SymlinkPolicy::Report,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, "NotFound");
+        assert_eq!(err.path, "/");
+    }
+
+    #[test]
+    fn scan_error_paths_are_relative() {
+        let err = ScanError::new(
+            Path::new("/srv/media/tv/Show 1/Season 1"),
+            &std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        let relative = err.clone().relative_to(Path::new("/srv/media/tv"));
+        assert_eq!(relative.path, "/Show 1/Season 1");
+        assert_eq!(relative.kind, "PermissionDenied");
+
+        // Paths outside the category only keep their name
+        let outside = err.relative_to(Path::new("/srv/media/movies"));
+        assert_eq!(outside.path, "/Season 1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn raw_names_round_trip() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"Caf\xe9 100%.mkv");
+        let raw_name = encode_raw_name(name).unwrap();
+        assert_eq!(raw_name, "Caf%E9 100%25.mkv");
+        assert_eq!(decode_raw_name(&raw_name).unwrap(), name);
+
+        assert!(encode_raw_name(OsStr::new("Movie (2023).mkv")).is_none());
+
+        // Only the listed components are decoded
+        let components = vec!["100%41 Movie".to_string(), raw_name];
+        let decoded = decode_path_components(&components, &[1]).unwrap();
+        assert_eq!(decoded[0], OsStr::new("100%41 Movie"));
+        assert_eq!(decoded[1], name);
+        assert!(decode_path_components(&components, &[2]).is_none());
+    }
+
+    #[test]
+    fn names_match_across_normalization_forms() {
+        let nfc = OsStr::new("Am\u{e9}lie (2001)");
+        let nfd = OsStr::new("Ame\u{301}lie (2001)");
+        assert!(names_match(nfc, nfd, false));
+        assert!(!names_match(nfc, OsStr::new("AM\u{c9}LIE (2001)"), false));
+        assert!(names_match(nfc, OsStr::new("AM\u{c9}LIE (2001)"), true));
+    }
+
+    #[test]
+    fn ignore_lines_honour_case_insensitive_prefix() {
+        assert!(ignore_line_matches("/Movie", "/Movie", false));
+        assert!(!ignore_line_matches("/movie", "/Movie", false));
+        assert!(ignore_line_matches("(?i)/movie", "/Movie", false));
+        assert!(ignore_line_matches("/movie", "/Movie", true));
+        assert!(ignore_line_matches("/Ame\u{301}lie", "/Am\u{e9}lie", false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn disk_usage_reports_mount_sizes() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let usage = disk_usage(temp_dir.path()).unwrap();
+        assert!(usage.device.is_some());
+        assert!(usage.total_bytes > 0);
+        assert!(usage.free_bytes <= usage.total_bytes);
+        assert!(usage.available_bytes <= usage.free_bytes);
+        assert!(usage.free_inodes <= usage.total_inodes);
+
+        assert!(disk_usage(&temp_dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn get_item_matches_full_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        create_library(&temp_dir);
+
+        let items = build_items(temp_dir.path(), SymlinkPolicy::Report);
+        let show = items.iter().find(|item| item.name == "Show 2").unwrap();
+        let season = show
+            .items
+            .iter()
+            .find(|item| item.name == "Season 1")
+            .unwrap();
+
+        let path = [OsString::from("Show 2"), OsString::from("Season 1")];
+        let found = get_item(temp_dir.path(), &path, SymlinkPolicy::Report, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, *season);
+
+        let escape = [OsString::from("Show 2"), OsString::from("..")];
+        assert!(
+            get_item(temp_dir.path(), &escape, SymlinkPolicy::Report, false)
+                .unwrap()
+                .is_none()
+        );
+
+        let system = [OsString::from(".stignore")];
+        assert!(
+            get_item(temp_dir.path(), &system, SymlinkPolicy::Report, false)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
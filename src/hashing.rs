@@ -0,0 +1,462 @@
+use crate::config::SymlinkPolicy;
+use crate::filesystem::{self, ListedFile, ScanError};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Most hashes kept in memory, enough for a large library without growing forever
+const MAX_CACHED_HASHES: usize = 100_000;
+
+/// A regular file found while walking a folder
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    /// Path components relative to the category, suitable for API requests
+    pub components: Vec<String>,
+    /// Indexes of the components given as percent-encoded raw names
+    pub raw_components: Vec<usize>,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// `(dev, inode)` of the file on Unix, so hard links aren't counted as duplicates
+    pub inode: Option<(u64, u64)>,
+}
+
+impl From<ListedFile> for FileEntry {
+    fn from(file: ListedFile) -> FileEntry {
+        FileEntry {
+            size: file.metadata.len(),
+            modified: file.metadata.modified().ok(),
+            inode: inode(&file.metadata),
+            path: file.path,
+            components: file.components,
+            raw_components: file.raw_components,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DuplicateFile {
+    pub category_id: String,
+    pub path: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
+}
+
+/// Files with identical content, and the space freed by keeping only one copy
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DuplicateGroup {
+    pub sha256: String,
+    pub size: u64,
+    pub files: Vec<DuplicateFile>,
+    pub reclaimable_bytes: u64,
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[derive(Debug, Clone)]
+struct CachedHash {
+    size: u64,
+    modified: Option<SystemTime>,
+    sha256: String,
+    /// Insert order, so the oldest hashes are evicted first
+    inserted: u64,
+}
+
+#[derive(Debug, Default)]
+struct CachedHashes {
+    hashes: HashMap<PathBuf, CachedHash>,
+    inserts: u64,
+}
+
+/// Previously computed hashes, only reused while a file's size and modification time are unchanged.
+/// Once full, hashes of deleted files are dropped first and then the oldest ones.
+#[derive(Debug, Clone)]
+pub struct HashCache {
+    cache: Arc<Mutex<CachedHashes>>,
+    max_entries: usize,
+}
+
+impl Default for HashCache {
+    fn default() -> HashCache {
+        HashCache::with_max_entries(MAX_CACHED_HASHES)
+    }
+}
+
+impl HashCache {
+    fn with_max_entries(max_entries: usize) -> HashCache {
+        HashCache {
+            cache: Default::default(),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    fn get(&self, file: &FileEntry) -> Option<String> {
+        let cache = self
+            .cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache
+            .hashes
+            .get(&file.path)
+            .filter(|cached| cached.size == file.size && cached.modified == file.modified)
+            .map(|cached| cached.sha256.clone())
+    }
+
+    fn insert(&self, file: &FileEntry, sha256: &str) {
+        let mut cache = self
+            .cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if !cache.hashes.contains_key(&file.path) && cache.hashes.len() >= self.max_entries {
+            cache
+                .hashes
+                .retain(|path, _| path.symlink_metadata().is_ok());
+        }
+        if !cache.hashes.contains_key(&file.path) && cache.hashes.len() >= self.max_entries {
+            // Evict a tenth at once, so a full cache isn't searched on every insert
+            let keep = self.max_entries - (self.max_entries / 10).max(1);
+            let mut inserted: Vec<u64> = cache.hashes.values().map(|c| c.inserted).collect();
+            inserted.sort_unstable();
+            let cutoff = inserted[inserted.len() - keep - 1];
+            cache.hashes.retain(|_, cached| cached.inserted > cutoff);
+        }
+
+        cache.inserts += 1;
+        let inserted = cache.inserts;
+        cache.hashes.insert(
+            file.path.clone(),
+            CachedHash {
+                size: file.size,
+                modified: file.modified,
+                sha256: sha256.to_string(),
+                inserted,
+            },
+        );
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .hashes
+            .len()
+    }
+}
+
+/// Lists the regular files under a path within a category, which may itself be a file,
+/// or the whole category for an empty path. See `filesystem::list_files`.
+pub fn collect_files(
+    category_path: &Path,
+    path: &[OsString],
+    symlinks: SymlinkPolicy,
+    case_insensitive: bool,
+) -> Result<Option<Vec<FileEntry>>, ScanError> {
+    let files = filesystem::list_files(category_path, path, symlinks, case_insensitive)?;
+    Ok(files.map(|files| files.into_iter().map(FileEntry::from).collect()))
+}
+
+/// Computes the SHA-256 of a file as lowercase hex, reusing the cached value when available
+pub fn hash_file(file: &FileEntry, cache: Option<&HashCache>) -> std::io::Result<String> {
+    if let Some(sha256) = cache.and_then(|cache| cache.get(file)) {
+        return Ok(sha256);
+    }
+
+    let mut reader = fs::File::open(&file.path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    let sha256: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    if let Some(cache) = cache {
+        cache.insert(file, &sha256);
+    }
+    Ok(sha256)
+}
+
+/// Finds files with identical content among the given `(category_id, file)` pairs.
+/// Hard links to one inode count as a single copy, hashed once. Copies are grouped by
+/// size first so only candidates that could match are hashed.
+pub fn find_duplicates(
+    files: Vec<(String, FileEntry)>,
+    cache: Option<&HashCache>,
+) -> Vec<DuplicateGroup> {
+    // Every path of each distinct copy, keyed by inode where known
+    let mut copies: Vec<Vec<(String, FileEntry)>> = vec![];
+    let mut by_inode: HashMap<(u64, u64), usize> = HashMap::new();
+    for (category_id, file) in files {
+        // Empty files are trivially identical and free nothing
+        if file.size == 0 {
+            continue;
+        }
+
+        match file.inode.and_then(|inode| by_inode.get(&inode)) {
+            Some(index) => copies[*index].push((category_id, file)),
+            None => {
+                if let Some(inode) = file.inode {
+                    by_inode.insert(inode, copies.len());
+                }
+                copies.push(vec![(category_id, file)]);
+            }
+        }
+    }
+
+    let mut by_size: HashMap<u64, Vec<Vec<(String, FileEntry)>>> = HashMap::new();
+    for copy in copies {
+        by_size.entry(copy[0].1.size).or_default().push(copy);
+    }
+
+    let candidates: Vec<Vec<(String, FileEntry)>> = by_size
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .flatten()
+        .collect();
+
+    let hashed: Vec<(String, Vec<(String, FileEntry)>)> = candidates
+        .into_par_iter()
+        .filter_map(|copy| match hash_file(&copy[0].1, cache) {
+            Ok(sha256) => Some((sha256, copy)),
+            Err(why) => {
+                tracing::warn!("Unable to hash {:?}: {:?}", copy[0].1.path, why.kind());
+                None
+            }
+        })
+        .collect();
+
+    let mut by_hash: HashMap<String, Vec<Vec<(String, FileEntry)>>> = HashMap::new();
+    for (sha256, copy) in hashed {
+        by_hash.entry(sha256).or_default().push(copy);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, group)| group.len() > 1)
+        .map(|(sha256, group)| {
+            let size = group[0][0].1.size;
+            let copies = group.len() as u64;
+
+            let mut files: Vec<DuplicateFile> = group
+                .into_iter()
+                .flatten()
+                .map(|(category_id, file)| DuplicateFile {
+                    category_id,
+                    path: file.components,
+                    raw_components: file.raw_components,
+                })
+                .collect();
+            files.sort_by(|a, b| (&a.category_id, &a.path).cmp(&(&b.category_id, &b.path)));
+
+            DuplicateGroup {
+                sha256,
+                size,
+                files,
+                reclaimable_bytes: size * (copies - 1),
+            }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| {
+        b.reclaimable_bytes
+            .cmp(&a.reclaimable_bytes)
+            .then_with(|| a.sha256.cmp(&b.sha256))
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // SHA-256 of "hello world"
+    const HELLO_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    fn list(category_path: &Path) -> Vec<FileEntry> {
+        collect_files(category_path, &[], SymlinkPolicy::Report, false)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn collect_files_skips_syncthing_items() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("Movie").join(".stversions")).unwrap();
+        fs::write(temp_dir.path().join("Movie").join("movie.mkv"), "movie").unwrap();
+        fs::write(temp_dir.path().join(".stignore"), "").unwrap();
+
+        let files = list(temp_dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].components, vec!["Movie", "movie.mkv"]);
+        assert_eq!(files[0].size, 5);
+
+        // A path inside the category lists only what's under it
+        let path = [OsString::from("Movie")];
+        let files = collect_files(temp_dir.path(), &path, SymlinkPolicy::Report, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        let path = [OsString::from("Missing")];
+        assert!(
+            collect_files(temp_dir.path(), &path, SymlinkPolicy::Report, false)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collect_files_follows_symlink_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("Movies")).unwrap();
+        fs::write(outside.path().join("movie.mkv"), "hello world").unwrap();
+        std::os::unix::fs::symlink(
+            outside.path(),
+            temp_dir.path().join("Movies").join("Linked"),
+        )
+        .unwrap();
+
+        assert!(list(temp_dir.path()).is_empty());
+
+        let files = collect_files(temp_dir.path(), &[], SymlinkPolicy::Follow, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].components, vec!["Movies", "Linked", "movie.mkv"]);
+        assert_eq!(hash_file(&files[0], None).unwrap(), HELLO_SHA256);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collect_files_keeps_raw_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.mkv");
+        fs::write(temp_dir.path().join(name), "hello world").unwrap();
+
+        let files = list(temp_dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].raw_components, vec![0]);
+        assert_eq!(files[0].path, temp_dir.path().join(name));
+        assert_eq!(hash_file(&files[0], None).unwrap(), HELLO_SHA256);
+    }
+
+    #[test]
+    fn hash_file_uses_cache_until_file_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("hello.txt");
+        fs::write(&path, "hello world").unwrap();
+
+        let cache = HashCache::default();
+        let file = list(temp_dir.path()).remove(0);
+        assert_eq!(hash_file(&file, Some(&cache)).unwrap(), HELLO_SHA256);
+
+        // A stale entry is returned while size and modification time match
+        cache.insert(&file, "cached");
+        assert_eq!(hash_file(&file, Some(&cache)).unwrap(), "cached");
+        assert_eq!(hash_file(&file, None).unwrap(), HELLO_SHA256);
+
+        fs::write(&path, "hello world, again").unwrap();
+        let changed = list(temp_dir.path()).remove(0);
+        assert_ne!(hash_file(&changed, Some(&cache)).unwrap(), "cached");
+    }
+
+    #[test]
+    fn hash_cache_evicts_deleted_then_oldest_files() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["a.mkv", "b.mkv", "c.mkv", "d.mkv"] {
+            fs::write(temp_dir.path().join(name), name).unwrap();
+        }
+        let files = list(temp_dir.path());
+        let file = |name: &str| {
+            files
+                .iter()
+                .find(|file| file.components == vec![name])
+                .unwrap()
+        };
+
+        let cache = HashCache::with_max_entries(2);
+        cache.insert(file("a.mkv"), "a");
+        cache.insert(file("b.mkv"), "b");
+
+        // A deleted file makes room without evicting anything else
+        fs::remove_file(temp_dir.path().join("a.mkv")).unwrap();
+        cache.insert(file("c.mkv"), "c");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(file("b.mkv")).unwrap(), "b");
+
+        // Otherwise the oldest hash goes
+        cache.insert(file("d.mkv"), "d");
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(file("b.mkv")).is_none());
+        assert_eq!(cache.get(file("c.mkv")).unwrap(), "c");
+        assert_eq!(cache.get(file("d.mkv")).unwrap(), "d");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_duplicates_ignores_hard_links() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.mkv"), "hello world").unwrap();
+        fs::write(temp_dir.path().join("b.mkv"), "hello world").unwrap();
+        fs::hard_link(temp_dir.path().join("a.mkv"), temp_dir.path().join("c.mkv")).unwrap();
+        fs::write(temp_dir.path().join("d.mkv"), "other world").unwrap();
+
+        let files = list(temp_dir.path())
+            .into_iter()
+            .map(|file| ("movies".to_string(), file))
+            .collect();
+
+        let groups = find_duplicates(files, None);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].sha256, HELLO_SHA256);
+        assert_eq!(groups[0].files.len(), 3);
+        // c.mkv shares a.mkv's inode, so only one copy can be freed
+        assert_eq!(groups[0].reclaimable_bytes, 11);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_duplicates_skips_hard_links_to_one_file() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.mkv"), "hello world").unwrap();
+        fs::hard_link(temp_dir.path().join("a.mkv"), temp_dir.path().join("b.mkv")).unwrap();
+
+        let files = list(temp_dir.path())
+            .into_iter()
+            .map(|file| ("movies".to_string(), file))
+            .collect();
+
+        // Both paths are one file, so there's nothing to free
+        assert!(find_duplicates(files, None).is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request, StatusCode, Uri};
+use hyper_util::client::legacy::{self, connect::HttpConnector};
+use hyper_util::rt::TokioExecutor;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+/// Failure talking to an HTTP API. Each service wraps it in its own error type,
+/// which names the service in messages.
+#[derive(Debug)]
+pub enum HttpError {
+    InvalidUrl(String),
+    Request(legacy::Error),
+    Body(hyper::Error),
+    Status(StatusCode),
+    Json(serde_json::Error),
+    Timeout,
+}
+
+impl HttpError {
+    /// Writes the error as coming from the named service, e.g. "Syncthing" or "agent"
+    pub(crate) fn describe(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        service: &str,
+    ) -> std::fmt::Result {
+        match self {
+            HttpError::InvalidUrl(url) => write!(f, "Invalid {} URL '{}'", service, url),
+            HttpError::Request(source) => write!(f, "Unable to reach {}: {}", service, source),
+            HttpError::Body(source) => {
+                write!(f, "Unable to read {} response: {}", service, source)
+            }
+            HttpError::Status(status) => {
+                write!(
+                    f,
+                    "{} responded with status {}",
+                    capitalized(service),
+                    status
+                )
+            }
+            HttpError::Json(source) => {
+                write!(f, "Unable to parse {} response: {}", service, source)
+            }
+            HttpError::Timeout => write!(f, "{} did not respond in time", capitalized(service)),
+        }
+    }
+
+    pub(crate) fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HttpError::Request(source) => Some(source),
+            HttpError::Body(source) => Some(source),
+            HttpError::Json(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn capitalized(service: &str) -> String {
+    let mut chars = service.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Parses a JSON response body
+pub fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, HttpError> {
+    serde_json::from_slice(body).map_err(HttpError::Json)
+}
+
+/// Client for a JSON API authenticated by an `X-API-Key` header, as served by both
+/// Syncthing and other agents. Only plain HTTP is supported.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    url: String,
+    api_key: String,
+    /// Longest time sending the request, and then reading the response, may each take
+    timeout: Duration,
+    http: legacy::Client<HttpConnector, Full<Bytes>>,
+}
+
+impl ApiClient {
+    pub fn new(url: &str, api_key: &str, timeout: Duration) -> ApiClient {
+        ApiClient {
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            timeout,
+            http: legacy::Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    /// Sends a request and returns the status and body, whether or not the status is a success
+    pub async fn request(
+        &self,
+        method: Method,
+        path_and_query: &str,
+        body: Vec<u8>,
+    ) -> Result<(StatusCode, Bytes), HttpError> {
+        let url = format!("{}{}", self.url, path_and_query);
+        let uri: Uri = url
+            .parse()
+            .map_err(|_| HttpError::InvalidUrl(url.clone()))?;
+        if uri.scheme_str() != Some("http") {
+            return Err(HttpError::InvalidUrl(url));
+        }
+
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("X-API-Key", &self.api_key)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|_| HttpError::InvalidUrl(url))?;
+
+        let response = tokio::time::timeout(self.timeout, self.http.request(request))
+            .await
+            .map_err(|_| HttpError::Timeout)?
+            .map_err(HttpError::Request)?;
+
+        let status = response.status();
+        let body = tokio::time::timeout(self.timeout, response.into_body().collect())
+            .await
+            .map_err(|_| HttpError::Timeout)?
+            .map_err(HttpError::Body)?;
+        Ok((status, body.to_bytes()))
+    }
+
+    /// Sends a request and returns the body, failing unless the status is a success
+    pub async fn send(
+        &self,
+        method: Method,
+        path_and_query: &str,
+        body: Vec<u8>,
+    ) -> Result<Bytes, HttpError> {
+        let (status, body) = self.request(method, path_and_query, body).await?;
+        if !status.is_success() {
+            return Err(HttpError::Status(status));
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote::RemoteError;
+    use crate::syncthing::SyncthingError;
+
+    #[tokio::test]
+    async fn errors_name_their_service() {
+        let client = ApiClient::new("https://localhost:8384", "key", Duration::from_secs(1));
+        let err = client.send(Method::GET, "/", vec![]).await.unwrap_err();
+        assert!(matches!(err, HttpError::InvalidUrl(_)));
+        assert_eq!(
+            SyncthingError(err).to_string(),
+            "Invalid Syncthing URL 'https://localhost:8384/'"
+        );
+
+        let status = HttpError::Status(StatusCode::FORBIDDEN);
+        assert_eq!(
+            RemoteError(status).to_string(),
+            "Agent responded with status 403 Forbidden"
+        );
+    }
+}
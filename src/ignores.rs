@@ -0,0 +1,371 @@
+use crate::config::{self, IgnoreBackend};
+use crate::error::ErrorCode;
+use crate::filesystem::{self, StignoreResult};
+use crate::syncthing;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub enum IgnoreError {
+    /// The category uses the Syncthing backend but no `[syncthing]` section is configured
+    NotConfigured,
+    Syncthing(syncthing::SyncthingError),
+}
+
+impl std::fmt::Display for IgnoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IgnoreError::NotConfigured => {
+                write!(
+                    f,
+                    "Syncthing ignores are selected but Syncthing isn't configured"
+                )
+            }
+            IgnoreError::Syncthing(source) => write!(f, "{}", source),
+        }
+    }
+}
+
+impl std::error::Error for IgnoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IgnoreError::NotConfigured => None,
+            IgnoreError::Syncthing(source) => Some(source),
+        }
+    }
+}
+
+/// Checks whether any of the ignore pattern lines covers the folder path
+pub fn matches(patterns: &[String], folder_path: &[String], case_insensitive: bool) -> bool {
+    matching_rule(patterns, folder_path, case_insensitive).is_some()
+}
+
+/// Returns the first ignore pattern line covering the folder path
+pub fn matching_rule<'a>(
+    patterns: &'a [String],
+    folder_path: &[String],
+    case_insensitive: bool,
+) -> Option<&'a str> {
+    let normalized_path = filesystem::build_unix_path_string(folder_path);
+    patterns
+        .iter()
+        .find(|line| filesystem::ignore_line_matches(line, &normalized_path, case_insensitive))
+        .map(|line| line.trim())
+}
+
+/// Serializes ignore changes per Syncthing folder, whichever backend writes them. Adding a
+/// rule writes back the whole list, so concurrent changes would otherwise overwrite each other.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreLocks(Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>);
+
+impl IgnoreLocks {
+    async fn lock(&self, folder_id: &str) -> tokio::sync::OwnedMutexGuard<()> {
+        let lock = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(folder_id.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+}
+
+/// Where a category's ignore patterns are read from and written to
+pub(crate) trait IgnoreStore {
+    /// Returns the ignore pattern lines currently in effect
+    async fn patterns(&self) -> Result<Vec<String>, IgnoreError>;
+
+    /// Adds a rule ignoring the folder path, unless an existing rule already covers it
+    async fn add(&self, folder_path: &[String]) -> StignoreResult;
+
+    fn case_insensitive(&self) -> bool;
+
+    async fn is_ignored(&self, folder_path: &[String]) -> Result<bool, IgnoreError> {
+        let patterns = self.patterns().await?;
+        Ok(matches(&patterns, folder_path, self.case_insensitive()))
+    }
+}
+
+/// Reads and writes the category's .stignore directly
+pub(crate) struct FileStore {
+    locks: IgnoreLocks,
+    folder_id: String,
+    base_path: PathBuf,
+    category_name: String,
+    case_insensitive: bool,
+}
+
+impl IgnoreStore for FileStore {
+    async fn patterns(&self) -> Result<Vec<String>, IgnoreError> {
+        // No .stignore file means nothing is ignored
+        let content = std::fs::read_to_string(self.base_path.join(".stignore")).unwrap_or_default();
+        Ok(content.lines().map(str::to_string).collect())
+    }
+
+    async fn add(&self, folder_path: &[String]) -> StignoreResult {
+        // Retention adds rules too, so requests and retention runs take turns rewriting the file
+        let _lock = self.locks.lock(&self.folder_id).await;
+
+        let base_path = self.base_path.clone();
+        let folder_path = folder_path.to_vec();
+        let category_name = self.category_name.clone();
+        let case_insensitive = self.case_insensitive;
+        tokio::task::spawn_blocking(move || {
+            filesystem::add_to_stignore(&base_path, &folder_path, &category_name, case_insensitive)
+        })
+        .await
+        .unwrap_or_else(|err| StignoreResult::Error {
+            code: ErrorCode::Internal,
+            message: format!("Failed to update .stignore: {}", err),
+        })
+    }
+
+    fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    async fn is_ignored(&self, folder_path: &[String]) -> Result<bool, IgnoreError> {
+        Ok(filesystem::is_path_ignored(
+            &self.base_path,
+            folder_path,
+            self.case_insensitive,
+        ))
+    }
+}
+
+/// Goes through Syncthing's `/rest/db/ignores`, so patterns are validated and
+/// written by Syncthing itself
+pub(crate) struct SyncthingStore {
+    client: syncthing::Client,
+    locks: IgnoreLocks,
+    folder_id: String,
+    category_name: String,
+    case_insensitive: bool,
+}
+
+impl IgnoreStore for SyncthingStore {
+    async fn patterns(&self) -> Result<Vec<String>, IgnoreError> {
+        self.client
+            .ignores(&self.folder_id)
+            .await
+            .map_err(IgnoreError::Syncthing)
+    }
+
+    async fn add(&self, folder_path: &[String]) -> StignoreResult {
+        // Held until the new list is written, so no other change lands in between
+        let _lock = self.locks.lock(&self.folder_id).await;
+
+        let mut patterns = match self.patterns().await {
+            Ok(patterns) => patterns,
+            Err(err) => {
+                return StignoreResult::Error {
+                    code: ErrorCode::SyncthingError,
+                    message: format!("Failed to read Syncthing ignores: {}", err),
+                };
+            }
+        };
+
+        let normalized_path = filesystem::build_unix_path_string(folder_path);
+        if matches(&patterns, folder_path, self.case_insensitive) {
+            return StignoreResult::AlreadyIgnored {
+                ignored_path: normalized_path,
+            };
+        }
+
+        patterns.push(match self.case_insensitive {
+            true => format!("(?i){}", normalized_path),
+            false => normalized_path.clone(),
+        });
+
+        match self.client.set_ignores(&self.folder_id, patterns).await {
+            Ok(()) => StignoreResult::Success {
+                message: format!(
+                    "Successfully added '{}' to Syncthing ignores in category '{}'",
+                    normalized_path, self.category_name
+                ),
+                ignored_path: normalized_path,
+            },
+            Err(err) => StignoreResult::Error {
+                code: ErrorCode::SyncthingError,
+                message: format!("Failed to update Syncthing ignores: {}", err),
+            },
+        }
+    }
+
+    fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+}
+
+/// The ignore store selected by a category's `ignore_backend`
+pub(crate) enum Ignores {
+    File(FileStore),
+    Syncthing(SyncthingStore),
+}
+
+impl Ignores {
+    pub(crate) fn for_category(
+        data: &config::Data,
+        category: &config::Category,
+    ) -> Result<Ignores, IgnoreError> {
+        let category_name = category.name.clone();
+        let case_insensitive = category.case_insensitive;
+
+        match category.ignore_backend {
+            IgnoreBackend::File => Ok(Ignores::File(FileStore {
+                locks: data.ignore_locks.clone(),
+                folder_id: category.folder_id().to_string(),
+                base_path: std::path::Path::new(&data.agent.base_path)
+                    .join(&category.relative_path),
+                category_name,
+                case_insensitive,
+            })),
+            IgnoreBackend::Syncthing => {
+                let client = data.syncthing_client().ok_or(IgnoreError::NotConfigured)?;
+                Ok(Ignores::Syncthing(SyncthingStore {
+                    client,
+                    locks: data.ignore_locks.clone(),
+                    folder_id: category.folder_id().to_string(),
+                    category_name,
+                    case_insensitive,
+                }))
+            }
+        }
+    }
+}
+
+impl IgnoreStore for Ignores {
+    async fn patterns(&self) -> Result<Vec<String>, IgnoreError> {
+        match self {
+            Ignores::File(store) => store.patterns().await,
+            Ignores::Syncthing(store) => store.patterns().await,
+        }
+    }
+
+    async fn add(&self, folder_path: &[String]) -> StignoreResult {
+        match self {
+            Ignores::File(store) => store.add(folder_path).await,
+            Ignores::Syncthing(store) => store.add(folder_path).await,
+        }
+    }
+
+    fn case_insensitive(&self) -> bool {
+        match self {
+            Ignores::File(store) => store.case_insensitive(),
+            Ignores::Syncthing(store) => store.case_insensitive(),
+        }
+    }
+
+    async fn is_ignored(&self, folder_path: &[String]) -> Result<bool, IgnoreError> {
+        match self {
+            Ignores::File(store) => store.is_ignored(folder_path).await,
+            Ignores::Syncthing(store) => store.is_ignored(folder_path).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syncthing::tests::mock_syncthing;
+
+    #[tokio::test]
+    async fn syncthing_store_adds_rules_once() {
+        let mock = mock_syncthing().await;
+        mock.ignores
+            .lock()
+            .unwrap()
+            .insert("movies".to_string(), vec!["// keep me".to_string()]);
+
+        let store = SyncthingStore {
+            client: syncthing::Client::new(&mock.config),
+            locks: IgnoreLocks::default(),
+            folder_id: "movies".to_string(),
+            category_name: "Movies".to_string(),
+            case_insensitive: true,
+        };
+        let folder_path = vec!["Movie 1 (2023)".to_string()];
+
+        assert!(!store.is_ignored(&folder_path).await.unwrap());
+        assert!(matches!(
+            store.add(&folder_path).await,
+            StignoreResult::Success { .. }
+        ));
+        assert!(
+            store
+                .is_ignored(&["movie 1 (2023)".to_string()])
+                .await
+                .unwrap()
+        );
+        assert!(matches!(
+            store.add(&folder_path).await,
+            StignoreResult::AlreadyIgnored { .. }
+        ));
+
+        // Existing lines are kept and the new rule is appended once
+        assert_eq!(
+            mock.ignores.lock().unwrap()["movies"],
+            vec!["// keep me", "(?i)/Movie 1 (2023)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn syncthing_store_keeps_concurrent_adds() {
+        let mock = mock_syncthing().await;
+        let locks = IgnoreLocks::default();
+        let store = |locks: &IgnoreLocks| SyncthingStore {
+            client: syncthing::Client::new(&mock.config),
+            locks: locks.clone(),
+            folder_id: "movies".to_string(),
+            category_name: "Movies".to_string(),
+            case_insensitive: false,
+        };
+
+        let adds: Vec<_> = (0..10)
+            .map(|index| {
+                let store = store(&locks);
+                tokio::spawn(async move { store.add(&[format!("Movie {}", index)]).await })
+            })
+            .collect();
+        for add in adds {
+            assert!(matches!(add.await.unwrap(), StignoreResult::Success { .. }));
+        }
+
+        // Every rule survives, however the requests interleaved
+        let mut patterns = mock.ignores.lock().unwrap()["movies"].clone();
+        patterns.sort();
+        let mut expected: Vec<String> = (0..10).map(|index| format!("/Movie {}", index)).collect();
+        expected.sort();
+        assert_eq!(patterns, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn file_store_keeps_concurrent_adds() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let locks = IgnoreLocks::default();
+        let store = |locks: &IgnoreLocks| FileStore {
+            locks: locks.clone(),
+            folder_id: "movies".to_string(),
+            base_path: temp_dir.path().to_path_buf(),
+            category_name: "Movies".to_string(),
+            case_insensitive: false,
+        };
+
+        let adds: Vec<_> = (0..10)
+            .map(|index| {
+                let store = store(&locks);
+                tokio::spawn(async move { store.add(&[format!("Movie {}", index)]).await })
+            })
+            .collect();
+        for add in adds {
+            assert!(matches!(add.await.unwrap(), StignoreResult::Success { .. }));
+        }
+
+        for index in 0..10 {
+            let folder_path = [format!("Movie {}", index)];
+            assert!(store(&locks).is_ignored(&folder_path).await.unwrap());
+        }
+    }
+}
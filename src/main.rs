@@ -1,28 +1,33 @@
+mod cleanup;
 mod config;
+mod controller;
+mod diff;
+mod error;
 mod filesystem;
+mod hashing;
+mod http;
+mod ignores;
 mod models;
+mod remote;
+mod retention;
+mod syncthing;
 mod tasks;
 
 use axum::{
-    Router,
-    body::Body,
-    extract::State,
-    http::{Request, StatusCode},
-    middleware,
-    response::Response,
-    routing::get,
-    routing::post,
+    Router, body::Body, extract::State, http::Request, middleware, response::Response,
+    routing::get, routing::post,
 };
+use error::{ApiError, ErrorCode};
 use tracing_subscriber::fmt;
 
 use std::env;
 use tokio::signal;
 
 async fn auth_middleware(
-    State(data): State<config::Data>,
+    State(api_key): State<String>,
     request: Request<Body>,
     next: middleware::Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     // Skip auth for help endpoint
     if request.uri().path() == "/" {
         return Ok(next.run(request).await);
@@ -35,10 +40,13 @@ async fn auth_middleware(
         .and_then(|header| header.to_str().ok());
 
     match auth_header {
-        Some(provided_key) if provided_key == data.agent.api_key => Ok(next.run(request).await),
+        Some(provided_key) if provided_key == api_key => Ok(next.run(request).await),
         _ => {
             tracing::warn!("Unauthorized access attempt to {}", request.uri().path());
-            Err(StatusCode::UNAUTHORIZED)
+            Err(ApiError::new(
+                ErrorCode::Unauthorized,
+                "Missing or invalid X-API-Key header",
+            ))
         }
     }
 }
@@ -78,13 +86,20 @@ async fn main() {
 
     /* load config */
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <config_file>", args[0]);
-        std::process::exit(1);
+    match args.get(1).map(String::as_str) {
+        Some("--controller") if args.len() == 3 => run_controller(&args[2]).await,
+        Some(config_filename) if args.len() == 2 && config_filename != "--controller" => {
+            run_agent(config_filename).await
+        }
+        _ => {
+            eprintln!("Usage: {} [--controller] <config_file>", args[0]);
+            std::process::exit(1);
+        }
     }
-    let config_filename = &args[1];
+}
 
-    let data = match config::load_config(config_filename) {
+async fn run_controller(config_filename: &str) {
+    let data = match config::load_controller_config(config_filename) {
         Ok(data) => data,
         Err(err) => {
             eprintln!("Failed to load configuration: {}", err);
@@ -92,11 +107,87 @@ async fn main() {
         }
     };
 
+    /* configure controller routes */
+    let app = Router::new()
+        .route("/", get(tasks::help))
+        .route("/api/v1/categories", get(controller::category_list))
+        .route("/api/v1/ignore", post(controller::post_ignore))
+        .route("/api/v1/delete", post(controller::post_delete))
+        .layer(middleware::from_fn_with_state(
+            data.controller.api_key.clone(),
+            auth_middleware,
+        ))
+        .with_state(data.clone());
+
+    /* bind to the port and listen */
+    let addr = format!("0.0.0.0:{}", data.controller.port);
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    tracing::info!(
+        "controller for {} agents listening on {}",
+        data.agents.len(),
+        &addr
+    );
+
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+}
+
+async fn run_agent(config_filename: &str) {
+    let mut data = match config::load_config(config_filename) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Failed to load configuration: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    /* add categories for the Syncthing folders on this device */
+    if let Some(syncthing) = data.syncthing.clone()
+        && let Some(discover) = &syncthing.discover
+    {
+        match syncthing::Client::new(&syncthing).folders().await {
+            Ok(folders) => {
+                let discovered = config::discovered_categories(
+                    &folders,
+                    &data.agent,
+                    discover,
+                    &data.categories,
+                );
+                tracing::info!("Discovered {} categories from Syncthing", discovered.len());
+                data.categories.extend(discovered);
+            }
+            Err(err) => {
+                tracing::warn!("Unable to discover categories from Syncthing: {}", err);
+            }
+        }
+    }
+
+    /* configure the directory scanner */
+    if let Err(err) = filesystem::init_scanner(data.agent.scan_threads) {
+        eprintln!("Failed to start directory scanner: {}", err);
+        std::process::exit(1);
+    }
+
+    /* start the retention job when any category has rules */
+    if data.categories.iter().any(|c| c.retention.is_some()) {
+        tokio::spawn(retention::run(data.clone()));
+    }
+
     /* configure application routes */
     let app = Router::new()
         .route("/", get(tasks::help))
         .route("/api/v1/categories", get(tasks::category_list))
         .route("/api/v1/categories/{id}", get(tasks::category_info))
+        .route(
+            "/api/v1/categories/{id}/local-changes",
+            get(tasks::category_local_changes),
+        )
+        .route(
+            "/api/v1/categories/{id}/revert",
+            post(tasks::post_category_revert),
+        )
         .route("/api/v1/items", post(tasks::post_item_info))
         .route("/api/v1/ignore", post(tasks::post_ignore))
         .route("/api/v1/ignore-status", post(tasks::post_ignore_status))
@@ -105,8 +196,15 @@ async fn main() {
             post(tasks::post_ignore_status_bulk),
         )
         .route("/api/v1/delete", post(tasks::post_delete))
+        .route("/api/v1/hashes", post(tasks::post_hashes))
+        .route("/api/v1/duplicates", get(tasks::duplicates))
+        .route("/api/v1/disk-usage", get(tasks::disk_usage))
+        .route("/api/v1/cleanup-plan", post(tasks::post_cleanup_plan))
+        .route("/api/v1/retention", get(tasks::retention_report))
+        .route("/api/v1/availability", post(tasks::post_availability))
+        .route("/api/v1/diff", post(tasks::post_diff))
         .layer(middleware::from_fn_with_state(
-            data.clone(),
+            data.agent.api_key.clone(),
             auth_middleware,
         ))
         .with_state(data.clone());
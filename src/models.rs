@@ -1,20 +1,46 @@
+use crate::cleanup;
+use crate::diff;
+use crate::error::ApiError;
 use crate::filesystem;
+use crate::hashing;
+use crate::retention;
+use crate::syncthing;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct CategoryListingResponse {
     pub items: Vec<filesystem::ItemGroup>,
+    /// Syncthing folder status per category, only when Syncthing is configured
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sync: Vec<CategorySyncState>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CategorySyncState {
+    pub category_id: String,
+    pub folder_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<syncthing::FolderStatus>,
+    /// Why the status is missing, e.g. Syncthing being unreachable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct CategoryInfoResponse {
     pub name: String,
     pub items: Vec<filesystem::ItemGroup>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<filesystem::ScanError>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct ItemInfoRequest {
     pub item_path: Vec<String>,
+    /// Indexes of the `item_path` components that are percent-encoded raw names,
+    /// which can't include the category ID
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,19 +48,19 @@ pub(crate) struct ItemInfoResponse {
     pub item: filesystem::ItemGroup,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub(crate) struct NotFoundResponse {
-    pub message: String,
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct IgnoreRequest {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    /// Indexes of the `folder_path` components that are percent-encoded raw names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct IgnoreResponse {
+    /// Always true, since failures are error responses. Kept for clients written
+    /// before errors had their own body.
     pub success: bool,
     pub message: String,
     pub ignored_path: Option<String>,
@@ -44,6 +70,9 @@ pub(crate) struct IgnoreResponse {
 pub(crate) struct IgnoreStatusRequest {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    /// Indexes of the `folder_path` components that are percent-encoded raw names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -60,7 +89,15 @@ pub(crate) struct BulkIgnoreStatusRequest {
 pub(crate) struct BulkIgnoreStatusItem {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
     pub ignored: bool,
+    /// The ignore rule covering the folder path, when it is ignored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_rule: Option<String>,
+    /// Why the status couldn't be checked, in which case `ignored` is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -72,11 +109,247 @@ pub(crate) struct BulkIgnoreStatusResponse {
 pub(crate) struct DeleteRequest {
     pub category_id: String,
     pub folder_path: Vec<String>,
+    /// Indexes of the `folder_path` components that are percent-encoded raw names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
+    /// Delete even though the path isn't ignored and Syncthing would delete it on other devices
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct DeleteResponse {
+    /// Always true, kept for older clients like `IgnoreResponse::success`
     pub success: bool,
     pub message: String,
     pub deleted_path: Option<String>,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct HashRequest {
+    pub category_id: String,
+    /// Folder or file to hash, an empty path hashes the whole category
+    pub folder_path: Vec<String>,
+    /// Indexes of `folder_path` components given as percent-encoded raw names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
+    /// Always re-read files instead of reusing hashes from previous calls
+    #[serde(default)]
+    pub skip_cache: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CategoryDiskUsage {
+    pub category_id: String,
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<filesystem::DiskUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<filesystem::ScanError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DiskUsageResponse {
+    pub categories: Vec<CategoryDiskUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CleanupPlanRequest {
+    pub category_id: String,
+    /// Free space the category's filesystem should have once the plan is carried out
+    pub target_free_bytes: u64,
+    #[serde(default)]
+    pub policy: cleanup::CleanupPolicy,
+    /// Paths already ignored on other peers, used by the `ignored_elsewhere` policy
+    #[serde(default)]
+    pub ignored_elsewhere: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct CleanupPlanResponse {
+    pub category_id: String,
+    pub available_bytes: u64,
+    pub target_free_bytes: u64,
+    pub needed_bytes: u64,
+    pub planned_bytes: u64,
+    /// False when every candidate together still falls short of the target
+    pub satisfied: bool,
+    pub steps: Vec<cleanup::CleanupStep>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct RetentionReportResponse {
+    pub runs: Vec<retention::RetentionRun>,
+}
+
+fn default_min_devices() -> usize {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct AvailabilityRequest {
+    pub category_id: String,
+    pub folder_path: Vec<String>,
+    /// Number of other devices that must hold the item for it to count as available
+    #[serde(default = "default_min_devices")]
+    pub min_devices: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DeviceAvailability {
+    pub device_id: String,
+    /// Percentage of the whole folder the device has synced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_completion: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_need_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct AvailabilityResponse {
+    pub category_id: String,
+    pub folder_path: Vec<String>,
+    /// Other devices holding the current version of the item in full
+    pub devices: Vec<DeviceAvailability>,
+    pub min_devices: usize,
+    /// True when at least `min_devices` other devices hold the item and reported their completion without error
+    pub available: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct LocalChangesResponse {
+    pub category_id: String,
+    pub folder_id: String,
+    pub items: Vec<syncthing::LocalChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct RevertResponse {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DiffRequest {
+    pub category_id: String,
+    /// The other agent's items of the category, as served by `/api/v1/categories/{id}`
+    #[serde(default)]
+    pub items: Option<Vec<filesystem::ItemGroup>>,
+    /// Paths the other agent ignores, ignore statuses are only compared when given
+    #[serde(default)]
+    pub ignored_paths: Option<Vec<Vec<String>>>,
+    /// Fetch the items and ignore statuses from this agent instead
+    #[serde(default)]
+    pub remote: Option<DiffRemote>,
+}
+
+/// Another agent to compare with. Any URL is accepted, so callers can make this
+/// agent send requests to hosts on its network.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DiffRemote {
+    pub url: String,
+    pub api_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DiffResponse {
+    pub category_id: String,
+    pub only_here: Vec<diff::DiffItem>,
+    pub only_there: Vec<diff::DiffItem>,
+    pub size_mismatches: Vec<diff::SizeMismatch>,
+    pub ignore_mismatches: Vec<diff::IgnoreMismatch>,
+    /// False when the other agent's ignore statuses weren't known, leaving `ignore_mismatches` empty
+    pub ignores_compared: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct DuplicatesResponse {
+    pub groups: Vec<hashing::DuplicateGroup>,
+    pub reclaimable_bytes: u64,
+}
+
+/// One line of the newline-delimited JSON stream returned when hashing
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct FileHashResponse {
+    pub path: Vec<String>,
+    /// Indexes of `path` components given as percent-encoded raw names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
+    pub size: u64,
+    pub sha256: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ControllerListingResponse {
+    pub agents: Vec<AgentSummary>,
+    pub categories: Vec<MergedCategory>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct AgentSummary {
+    pub name: String,
+    pub url: String,
+    /// Why the agent is missing from the merged view, or why its ignore statuses are unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct MergedCategory {
+    pub id: String,
+    pub name: String,
+    pub items: Vec<MergedItem>,
+}
+
+/// An item found on at least one agent, along with what every reachable agent has of it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct MergedItem {
+    pub name: String,
+    pub kind: filesystem::ItemKind,
+    pub agents: Vec<AgentItemState>,
+    pub items: Vec<MergedItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct AgentItemState {
+    pub agent: String,
+    pub present: bool,
+    pub ignored: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_kb: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ControllerIgnoreRequest {
+    /// Names of the agents to forward the request to
+    pub agents: Vec<String>,
+    #[serde(flatten)]
+    pub request: IgnoreRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ControllerDeleteRequest {
+    /// Names of the agents to forward the request to
+    pub agents: Vec<String>,
+    #[serde(flatten)]
+    pub request: DeleteRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct FanOutResponse {
+    pub results: Vec<AgentResult>,
+}
+
+/// The response of one agent to a forwarded request
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct AgentResult {
+    pub agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<serde_json::Value>,
+    /// Why the agent couldn't be asked, e.g. it being unreachable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
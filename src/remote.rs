@@ -0,0 +1,105 @@
+use crate::http::{self, ApiClient, HttpError};
+use crate::models::{
+    BulkIgnoreStatusRequest, BulkIgnoreStatusResponse, CategoryInfoResponse,
+    CategoryListingResponse,
+};
+use hyper::{Method, StatusCode};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::{Serialize, de::DeserializeOwned};
+use std::time::Duration;
+
+/// Longest time a request to another agent may take, long enough for it to scan its categories
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct RemoteError(pub HttpError);
+
+impl From<HttpError> for RemoteError {
+    fn from(err: HttpError) -> RemoteError {
+        RemoteError(err)
+    }
+}
+
+impl std::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.describe(f, "agent")
+    }
+}
+
+impl std::error::Error for RemoteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Client for the API of another stignore-agent. Like the Syncthing client,
+/// only plain HTTP is supported.
+#[derive(Debug, Clone)]
+pub struct AgentClient {
+    http: ApiClient,
+}
+
+impl AgentClient {
+    pub fn new(url: &str, api_key: &str) -> AgentClient {
+        AgentClient {
+            http: ApiClient::new(url, api_key, REQUEST_TIMEOUT),
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, RemoteError> {
+        let body = self.http.send(Method::GET, path, vec![]).await?;
+        Ok(http::parse(&body)?)
+    }
+
+    async fn post_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        payload: &impl Serialize,
+    ) -> Result<T, RemoteError> {
+        let (status, body) = self.forward(path, payload).await?;
+        if !status.is_success() {
+            return Err(HttpError::Status(status).into());
+        }
+        serde_json::from_value(body).map_err(|err| HttpError::Json(err).into())
+    }
+
+    /// Posts a request and hands back the agent's status and JSON body as-is,
+    /// so callers can relay failures reported by the agent
+    pub(crate) async fn forward(
+        &self,
+        path: &str,
+        payload: &impl Serialize,
+    ) -> Result<(StatusCode, serde_json::Value), RemoteError> {
+        let payload = serde_json::to_vec(payload).map_err(HttpError::Json)?;
+        let (status, body) = self.http.request(Method::POST, path, payload).await?;
+        let body = match body.is_empty() {
+            true => serde_json::Value::Null,
+            false => http::parse(&body)?,
+        };
+        Ok((status, body))
+    }
+
+    /// Lists every category of the agent, as served by `/api/v1/categories`
+    pub(crate) async fn categories(&self) -> Result<CategoryListingResponse, RemoteError> {
+        self.get_json("/api/v1/categories").await
+    }
+
+    /// Returns the items of one category, as served by `/api/v1/categories/{id}`
+    pub(crate) async fn category(
+        &self,
+        category_id: &str,
+    ) -> Result<CategoryInfoResponse, RemoteError> {
+        let path = format!(
+            "/api/v1/categories/{}",
+            utf8_percent_encode(category_id, NON_ALPHANUMERIC)
+        );
+        self.get_json(&path).await
+    }
+
+    pub(crate) async fn ignore_status_bulk(
+        &self,
+        request: &BulkIgnoreStatusRequest,
+    ) -> Result<BulkIgnoreStatusResponse, RemoteError> {
+        self.post_json("/api/v1/ignore-status-bulk", request).await
+    }
+}
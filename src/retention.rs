@@ -0,0 +1,516 @@
+use crate::cleanup::{self, Candidate};
+use crate::config::{self, RetentionMode, RetentionPolicy};
+use crate::filesystem::{self, DeleteResult, ItemGroup, ItemKind, ScanError, StignoreResult};
+use crate::ignores::{IgnoreStore, Ignores};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Number of runs kept in memory for the retention report
+const MAX_RUNS: usize = 100;
+
+const DEFAULT_INTERVAL_MINUTES: u64 = 60;
+
+/// The rule an item broke
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionReason {
+    KeepNewest,
+    MaxAge,
+    MaxSize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionStatus {
+    /// Found in report mode and left alone
+    Reported,
+    /// Ignored and then deleted
+    Deleted,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct RetentionAction {
+    pub folder_path: Vec<String>,
+    /// Indexes of the `folder_path` components that are raw names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_components: Vec<usize>,
+    pub reason: RetentionReason,
+    pub reclaimable_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newest_modified: Option<u64>,
+    pub status: RetentionStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// One evaluation of a category's retention rules
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct RetentionRun {
+    pub category_id: String,
+    /// Start of the run, in seconds since the Unix epoch
+    pub started_at: u64,
+    pub mode: RetentionMode,
+    pub actions: Vec<RetentionAction>,
+    /// Set when the category could not be scanned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ScanError>,
+}
+
+/// Recent retention runs, shared by the background job and the report endpoint
+#[derive(Debug, Clone, Default)]
+pub struct RetentionLog(Arc<Mutex<VecDeque<RetentionRun>>>);
+
+impl RetentionLog {
+    fn record(&self, run: RetentionRun) {
+        let mut runs = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        runs.push_front(run);
+        runs.truncate(MAX_RUNS);
+    }
+
+    /// Returns the recorded runs, newest first
+    pub(crate) fn runs(&self) -> Vec<RetentionRun> {
+        let runs = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        runs.iter().cloned().collect()
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Orders candidates newest first, with items lacking a modification time last
+fn newest_first(a: &Candidate, b: &Candidate) -> std::cmp::Ordering {
+    b.newest_modified()
+        .cmp(&a.newest_modified())
+        .then_with(|| b.path.cmp(&a.path))
+}
+
+/// Finds the items of a scanned category that break its retention rules.
+/// Rules are applied in turn, each only considering items the previous ones kept.
+pub(crate) fn find_violations<'a>(
+    category: &'a ItemGroup,
+    policy: &RetentionPolicy,
+    now: u64,
+) -> Vec<(Candidate<'a>, RetentionReason)> {
+    let mut candidates = vec![];
+    cleanup::collect_leaf_candidates(&category.items, &[], &[], &mut candidates);
+
+    let mut kept = vec![];
+    let mut violations = vec![];
+
+    match policy.keep_newest {
+        Some(keep_newest) => {
+            // Only directories within another item count, so artwork or an NFO file
+            // next to the seasons can never push a season out
+            let mut by_parent: BTreeMap<Vec<String>, Vec<Candidate>> = BTreeMap::new();
+            for candidate in candidates {
+                if candidate.item.kind != ItemKind::Dir || candidate.path.len() < 2 {
+                    kept.push(candidate);
+                    continue;
+                }
+
+                let parent = candidate.path[..candidate.path.len() - 1].to_vec();
+                by_parent.entry(parent).or_default().push(candidate);
+            }
+
+            for mut group in by_parent.into_values() {
+                group.sort_by(newest_first);
+                let removed = group.split_off(keep_newest.min(group.len()));
+                kept.extend(group);
+                violations.extend(
+                    removed
+                        .into_iter()
+                        .map(|candidate| (candidate, RetentionReason::KeepNewest)),
+                );
+            }
+        }
+        None => kept = candidates,
+    }
+
+    if let Some(max_age_days) = policy.max_age_days {
+        let cutoff = now.saturating_sub(max_age_days.saturating_mul(24 * 60 * 60));
+        let (expired, fresh): (Vec<_>, Vec<_>) = kept.into_iter().partition(|candidate| {
+            candidate
+                .newest_modified()
+                .is_some_and(|time| time < cutoff)
+        });
+        kept = fresh;
+        violations.extend(
+            expired
+                .into_iter()
+                .map(|candidate| (candidate, RetentionReason::MaxAge)),
+        );
+    }
+
+    if let Some(max_size_gb) = policy.max_size_gb {
+        let max_bytes = max_size_gb.saturating_mul(1024 * 1024 * 1024);
+        let mut remaining_bytes = (category.unique_size_kb * 1024).saturating_sub(
+            violations
+                .iter()
+                .map(|(candidate, _)| candidate.reclaimable_bytes())
+                .sum(),
+        );
+
+        kept.sort_by(newest_first);
+        while remaining_bytes > max_bytes {
+            let Some(oldest) = kept.pop() else {
+                break;
+            };
+            remaining_bytes = remaining_bytes.saturating_sub(oldest.reclaimable_bytes());
+            violations.push((oldest, RetentionReason::MaxSize));
+        }
+    }
+
+    violations
+}
+
+/// Ignores and then deletes an item, so Syncthing doesn't bring it back
+async fn enforce(
+    data: &config::Data,
+    category: &config::Category,
+    action: &RetentionAction,
+) -> (RetentionStatus, Option<String>) {
+    if !action.raw_components.is_empty() {
+        return (
+            RetentionStatus::Failed,
+            Some("Items with non-UTF-8 names can't be ignored".to_string()),
+        );
+    }
+
+    let ignores = match Ignores::for_category(data, category) {
+        Ok(ignores) => ignores,
+        Err(err) => return (RetentionStatus::Failed, Some(err.to_string())),
+    };
+    if let StignoreResult::Error { message, .. } = ignores.add(&action.folder_path).await {
+        return (RetentionStatus::Failed, Some(message));
+    }
+
+    let category_path = std::path::Path::new(&data.agent.base_path).join(&category.relative_path);
+    let components: Vec<std::ffi::OsString> = action.folder_path.iter().map(Into::into).collect();
+    let category_name = category.name.clone();
+    let symlinks = data.agent.symlinks;
+    let case_insensitive = category.case_insensitive;
+    let deleted = tokio::task::spawn_blocking(move || {
+        filesystem::delete_from_filesystem(
+            &category_path,
+            &components,
+            &category_name,
+            symlinks,
+            case_insensitive,
+        )
+    })
+    .await;
+
+    match deleted {
+        Ok(DeleteResult::Success { .. }) => (RetentionStatus::Deleted, None),
+        Ok(DeleteResult::NotFound { requested_path }) => (
+            RetentionStatus::Failed,
+            Some(format!("Path '{}' no longer exists", requested_path)),
+        ),
+        Ok(DeleteResult::Error { message }) => (RetentionStatus::Failed, Some(message)),
+        Err(err) => (RetentionStatus::Failed, Some(err.to_string())),
+    }
+}
+
+/// Scans a category and reports the items that break its rules, without touching them
+fn find_run(
+    data: &config::Data,
+    category: &config::Category,
+    policy: &RetentionPolicy,
+) -> RetentionRun {
+    let started_at = now_secs();
+    let mut run = RetentionRun {
+        category_id: category.id.clone(),
+        started_at,
+        mode: policy.mode,
+        actions: vec![],
+        error: None,
+    };
+
+    let category_path = std::path::Path::new(&data.agent.base_path).join(&category.relative_path);
+    let scanned = match filesystem::scan_items(
+        &category_path,
+        category.id.clone(),
+        category.name.clone(),
+        data.agent.symlinks,
+    ) {
+        Ok(scanned) => scanned,
+        Err(err) => {
+            run.error = Some(err);
+            return run;
+        }
+    };
+
+    for (candidate, reason) in find_violations(&scanned, policy, started_at) {
+        run.actions.push(RetentionAction {
+            raw_components: candidate.raw_components(),
+            reason,
+            reclaimable_bytes: candidate.reclaimable_bytes(),
+            newest_modified: candidate.newest_modified(),
+            status: RetentionStatus::Reported,
+            message: None,
+            folder_path: candidate.raw_path,
+        });
+    }
+
+    run
+}
+
+async fn run_category(
+    data: &config::Data,
+    category: &config::Category,
+    policy: &RetentionPolicy,
+) -> RetentionRun {
+    let (scan_data, scan_category, scan_policy) = (data.clone(), category.clone(), policy.clone());
+    let mut run = match tokio::task::spawn_blocking(move || {
+        find_run(&scan_data, &scan_category, &scan_policy)
+    })
+    .await
+    {
+        Ok(run) => run,
+        Err(err) => {
+            tracing::error!(
+                "Retention run for category '{}' failed: {}",
+                category.id,
+                err
+            );
+            return RetentionRun {
+                category_id: category.id.clone(),
+                started_at: now_secs(),
+                mode: policy.mode,
+                actions: vec![],
+                error: None,
+            };
+        }
+    };
+
+    if policy.mode == RetentionMode::Enforce {
+        for action in run.actions.iter_mut() {
+            (action.status, action.message) = enforce(data, category, action).await;
+        }
+    }
+
+    for action in &run.actions {
+        tracing::info!(
+            "Retention for category '{}': {:?} {:?} ({:?})",
+            category.id,
+            action.status,
+            action.folder_path,
+            action.reason
+        );
+    }
+
+    // Have Syncthing notice the deletions without waiting for its next scan
+    let deleted = run
+        .actions
+        .iter()
+        .any(|action| action.status == RetentionStatus::Deleted);
+    if let Some(client) = data.syncthing_client().filter(|_| deleted)
+        && let Err(err) = client.scan(category.folder_id()).await
+    {
+        tracing::warn!(
+            "Unable to rescan Syncthing folder '{}': {}",
+            category.folder_id(),
+            err
+        );
+    }
+
+    run
+}
+
+/// Evaluates the retention rules of every category that has them, recording each run
+pub async fn run_once(data: &config::Data) {
+    for category in &data.categories {
+        if let Some(policy) = &category.retention {
+            let run = run_category(data, category, policy).await;
+            data.retention_log.record(run);
+        }
+    }
+}
+
+/// Background job evaluating retention rules on the configured interval
+pub async fn run(data: config::Data) {
+    let minutes = data
+        .agent
+        .retention_interval_minutes
+        .unwrap_or(DEFAULT_INTERVAL_MINUTES)
+        .max(1);
+    let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+
+    loop {
+        interval.tick().await;
+        run_once(&data).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn season(name: &str, size_kb: u64, modified: u64) -> ItemGroup {
+        serde_json::from_value(serde_json::json!({
+            "id": name,
+            "name": name,
+            "kind": ItemKind::Dir,
+            "size_kb": size_kb,
+            "unique_size_kb": size_kb,
+            "reclaimable_size_kb": size_kb,
+            "items": [],
+            "leaf": true,
+            "newest_modified": modified,
+        }))
+        .unwrap()
+    }
+
+    fn show(name: &str, seasons: Vec<ItemGroup>) -> ItemGroup {
+        filesystem::group_items(name.to_string(), name.to_string(), seasons)
+    }
+
+    fn library() -> ItemGroup {
+        filesystem::group_items(
+            "tv".to_string(),
+            "TV".to_string(),
+            vec![
+                show(
+                    "Show 1",
+                    vec![
+                        season("Season 1", 100, 10 * DAY),
+                        season("Season 2", 100, 20 * DAY),
+                        season("Season 3", 100, 30 * DAY),
+                    ],
+                ),
+                show("Show 2", vec![season("Season 1", 500, 5 * DAY)]),
+            ],
+        )
+    }
+
+    fn violations(policy: &RetentionPolicy) -> Vec<(Vec<String>, RetentionReason)> {
+        let library = library();
+        find_violations(&library, policy, 40 * DAY)
+            .into_iter()
+            .map(|(candidate, reason)| (candidate.path, reason))
+            .collect()
+    }
+
+    fn path(show: &str, season: &str) -> Vec<String> {
+        vec![show.to_string(), season.to_string()]
+    }
+
+    #[test]
+    fn keep_newest_applies_per_top_level_item() {
+        let policy = RetentionPolicy {
+            keep_newest: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            violations(&policy),
+            vec![
+                (path("Show 1", "Season 2"), RetentionReason::KeepNewest),
+                (path("Show 1", "Season 1"), RetentionReason::KeepNewest),
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_newest_only_counts_directories() {
+        let file = |name: &str, modified: u64| -> ItemGroup {
+            serde_json::from_value(serde_json::json!({
+                "id": name,
+                "name": name,
+                "kind": ItemKind::File,
+                "size_kb": 1,
+                "items": [],
+                "leaf": false,
+                "modified": modified,
+            }))
+            .unwrap()
+        };
+        let library = filesystem::group_items(
+            "tv".to_string(),
+            "TV".to_string(),
+            vec![
+                show(
+                    "Show 1",
+                    vec![
+                        season("Season 1", 100, 10 * DAY),
+                        season("Season 2", 100, 20 * DAY),
+                        file("poster.jpg", 35 * DAY),
+                        file("tvshow.nfo", 36 * DAY),
+                    ],
+                ),
+                season("Movie", 100, DAY),
+            ],
+        );
+
+        let policy = RetentionPolicy {
+            keep_newest: Some(1),
+            ..Default::default()
+        };
+        let violations: Vec<_> = find_violations(&library, &policy, 40 * DAY)
+            .into_iter()
+            .map(|(candidate, reason)| (candidate.path, reason))
+            .collect();
+        assert_eq!(
+            violations,
+            vec![(path("Show 1", "Season 1"), RetentionReason::KeepNewest)]
+        );
+    }
+
+    #[test]
+    fn large_limits_never_overflow() {
+        let policy = RetentionPolicy {
+            max_age_days: Some(u64::MAX),
+            max_size_gb: Some(u64::MAX),
+            ..Default::default()
+        };
+        assert!(violations(&policy).is_empty());
+    }
+
+    #[test]
+    fn rules_apply_to_items_kept_by_earlier_rules() {
+        let policy = RetentionPolicy {
+            keep_newest: Some(2),
+            max_age_days: Some(30),
+            max_size_gb: None,
+            mode: RetentionMode::Report,
+        };
+        assert_eq!(
+            violations(&policy),
+            vec![
+                (path("Show 1", "Season 1"), RetentionReason::KeepNewest),
+                (path("Show 2", "Season 1"), RetentionReason::MaxAge),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_size_removes_oldest_items_until_within_limit() {
+        let policy = RetentionPolicy {
+            max_size_gb: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            violations(&policy),
+            vec![
+                (path("Show 2", "Season 1"), RetentionReason::MaxSize),
+                (path("Show 1", "Season 1"), RetentionReason::MaxSize),
+                (path("Show 1", "Season 2"), RetentionReason::MaxSize),
+                (path("Show 1", "Season 3"), RetentionReason::MaxSize),
+            ]
+        );
+    }
+}
@@ -0,0 +1,629 @@
+use crate::config::{FolderType, SyncthingConfig};
+use crate::http::{self, ApiClient, HttpError};
+use hyper::Method;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Longest time any Syncthing request may take, so an unresponsive Syncthing can't stall the API
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct SyncthingError(pub HttpError);
+
+impl From<HttpError> for SyncthingError {
+    fn from(err: HttpError) -> SyncthingError {
+        SyncthingError(err)
+    }
+}
+
+/// A folder as listed by `/rest/config/folders`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FolderConfig {
+    pub id: String,
+    #[serde(default)]
+    pub label: String,
+    pub path: String,
+    #[serde(default, rename = "type")]
+    pub folder_type: FolderType,
+}
+
+/// Sync progress of a folder, from `/rest/db/status`. Fields are snake_case in
+/// our API and accepted in the camelCase Syncthing sends.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct FolderStatus {
+    /// e.g. "idle", "scanning", "syncing" or "error"
+    pub state: String,
+    #[serde(default, alias = "stateChanged")]
+    pub state_changed: Option<String>,
+    #[serde(default, alias = "globalBytes")]
+    pub global_bytes: u64,
+    #[serde(default, alias = "localBytes")]
+    pub local_bytes: u64,
+    #[serde(default, alias = "inSyncBytes")]
+    pub in_sync_bytes: u64,
+    /// Bytes still to be downloaded before the folder is in sync
+    #[serde(default, alias = "needBytes")]
+    pub need_bytes: u64,
+    #[serde(default, alias = "needFiles")]
+    pub need_files: u64,
+    #[serde(default, alias = "needDeletes")]
+    pub need_deletes: u64,
+}
+
+/// A device holding a file, from the `availability` list of `/rest/db/file`
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct Availability {
+    pub id: String,
+    /// Set while the device only has a partial, temporary copy
+    #[serde(default, rename = "fromTemporary")]
+    pub from_temporary: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileInfo {
+    #[serde(default)]
+    availability: Option<Vec<Availability>>,
+}
+
+/// How far a device is with a folder, from `/rest/db/completion`
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct Completion {
+    /// Percentage of the folder the device has, from 0 to 100
+    pub completion: f64,
+    #[serde(default, rename = "needBytes")]
+    pub need_bytes: u64,
+}
+
+/// An item changed locally in a receive-only folder, from `/rest/db/localchanged`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct LocalChange {
+    pub name: String,
+    #[serde(default)]
+    pub size: i64,
+    /// Set for items deleted locally, which a revert brings back
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub modified: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocalChangesPage {
+    #[serde(default)]
+    files: Option<Vec<LocalChange>>,
+}
+
+/// Page size used when listing local changes
+const LOCAL_CHANGES_PER_PAGE: usize = 1000;
+/// Most pages of local changes read for one listing
+const MAX_LOCAL_CHANGES_PAGES: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct SystemStatus {
+    #[serde(rename = "myID")]
+    my_id: String,
+}
+
+type FetchedStatus = (Instant, Result<FolderStatus, String>);
+
+/// Folder statuses fetched within the last TTL, including failures so an
+/// unreachable Syncthing isn't asked again on every request
+#[derive(Debug, Clone, Default)]
+pub struct FolderStatusCache(Arc<Mutex<HashMap<String, FetchedStatus>>>);
+
+impl FolderStatusCache {
+    pub(crate) async fn get(
+        &self,
+        client: &Client,
+        folder_id: &str,
+        ttl: Duration,
+    ) -> Result<FolderStatus, String> {
+        {
+            let cache = self
+                .0
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some((fetched, status)) = cache.get(folder_id)
+                && fetched.elapsed() < ttl
+            {
+                return status.clone();
+            }
+        }
+
+        let status = client
+            .folder_status(folder_id)
+            .await
+            .map_err(|err| err.to_string());
+
+        let mut cache = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.insert(folder_id.to_string(), (Instant::now(), status.clone()));
+        status
+    }
+}
+
+/// Body of `/rest/db/ignores`, where `ignore` is null for folders without a .stignore
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Ignores {
+    #[serde(default)]
+    ignore: Option<Vec<String>>,
+}
+
+impl std::fmt::Display for SyncthingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.describe(f, "Syncthing")
+    }
+}
+
+impl std::error::Error for SyncthingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Client for the REST API of the Syncthing instance sharing this agent's folders.
+/// Only plain HTTP is supported, which covers the usual local GUI address.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: ApiClient,
+}
+
+impl Client {
+    pub fn new(config: &SyncthingConfig) -> Client {
+        Client {
+            http: ApiClient::new(&config.url, &config.api_key, REQUEST_TIMEOUT),
+        }
+    }
+
+    fn folder_query(path: &str, folder_id: &str) -> String {
+        format!(
+            "{}?folder={}",
+            path,
+            utf8_percent_encode(folder_id, NON_ALPHANUMERIC)
+        )
+    }
+
+    /// Asks Syncthing to rescan a folder, which also makes it reload the folder's .stignore
+    pub async fn scan(&self, folder_id: &str) -> Result<(), SyncthingError> {
+        let path = Client::folder_query("/rest/db/scan", folder_id);
+        self.http.send(Method::POST, &path, vec![]).await?;
+        Ok(())
+    }
+
+    /// Lists the folders configured in Syncthing
+    pub async fn folders(&self) -> Result<Vec<FolderConfig>, SyncthingError> {
+        let body = self
+            .http
+            .send(Method::GET, "/rest/config/folders", vec![])
+            .await?;
+        Ok(http::parse(&body)?)
+    }
+
+    pub(crate) async fn folder_status(
+        &self,
+        folder_id: &str,
+    ) -> Result<FolderStatus, SyncthingError> {
+        let path = Client::folder_query("/rest/db/status", folder_id);
+        let body = self.http.send(Method::GET, &path, vec![]).await?;
+        Ok(http::parse(&body)?)
+    }
+
+    /// Returns the ID of the device this Syncthing instance runs as
+    pub(crate) async fn my_id(&self) -> Result<String, SyncthingError> {
+        let body = self
+            .http
+            .send(Method::GET, "/rest/system/status", vec![])
+            .await?;
+        let status: SystemStatus = http::parse(&body)?;
+        Ok(status.my_id)
+    }
+
+    /// Lists the devices holding the current version of a file or directory,
+    /// given by its path within the folder
+    pub(crate) async fn availability(
+        &self,
+        folder_id: &str,
+        file: &str,
+    ) -> Result<Vec<Availability>, SyncthingError> {
+        let path = format!(
+            "{}&file={}",
+            Client::folder_query("/rest/db/file", folder_id),
+            utf8_percent_encode(file, NON_ALPHANUMERIC)
+        );
+        let body = self.http.send(Method::GET, &path, vec![]).await?;
+        let info: FileInfo = http::parse(&body)?;
+        Ok(info.availability.unwrap_or_default())
+    }
+
+    pub(crate) async fn completion(
+        &self,
+        folder_id: &str,
+        device_id: &str,
+    ) -> Result<Completion, SyncthingError> {
+        let path = format!(
+            "{}&device={}",
+            Client::folder_query("/rest/db/completion", folder_id),
+            utf8_percent_encode(device_id, NON_ALPHANUMERIC)
+        );
+        let body = self.http.send(Method::GET, &path, vec![]).await?;
+        Ok(http::parse(&body)?)
+    }
+
+    /// Lists every locally changed item of a receive-only folder
+    pub(crate) async fn local_changes(
+        &self,
+        folder_id: &str,
+    ) -> Result<Vec<LocalChange>, SyncthingError> {
+        let mut changes = vec![];
+        let mut seen = HashSet::new();
+        for page in 1..=MAX_LOCAL_CHANGES_PAGES {
+            let path = format!(
+                "{}&page={}&perpage={}",
+                Client::folder_query("/rest/db/localchanged", folder_id),
+                page,
+                LOCAL_CHANGES_PER_PAGE
+            );
+            let body = self.http.send(Method::GET, &path, vec![]).await?;
+            let page: LocalChangesPage = http::parse(&body)?;
+            let files = page.files.unwrap_or_default();
+
+            // A page with nothing new means Syncthing isn't paging, so asking again won't help
+            let last_page = files.len() < LOCAL_CHANGES_PER_PAGE;
+            let known = changes.len();
+            changes.extend(
+                files
+                    .into_iter()
+                    .filter(|file| seen.insert(file.name.clone())),
+            );
+            if last_page || changes.len() == known {
+                break;
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Undoes the local changes of a receive-only folder, bringing back deleted items
+    pub(crate) async fn revert(&self, folder_id: &str) -> Result<(), SyncthingError> {
+        let path = Client::folder_query("/rest/db/revert", folder_id);
+        self.http.send(Method::POST, &path, vec![]).await?;
+        Ok(())
+    }
+
+    /// Returns the lines of a folder's ignore patterns, as Syncthing has them loaded
+    pub async fn ignores(&self, folder_id: &str) -> Result<Vec<String>, SyncthingError> {
+        let path = Client::folder_query("/rest/db/ignores", folder_id);
+        let body = self.http.send(Method::GET, &path, vec![]).await?;
+        let ignores: Ignores = http::parse(&body)?;
+        Ok(ignores.ignore.unwrap_or_default())
+    }
+
+    /// Replaces a folder's ignore patterns, which Syncthing validates and writes to .stignore
+    pub async fn set_ignores(
+        &self,
+        folder_id: &str,
+        lines: Vec<String>,
+    ) -> Result<(), SyncthingError> {
+        let path = Client::folder_query("/rest/db/ignores", folder_id);
+        let body = serde_json::to_vec(&Ignores {
+            ignore: Some(lines),
+        })
+        .map_err(HttpError::Json)?;
+        self.http.send(Method::POST, &path, body).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use axum::{
+        Json, Router,
+        extract::{Query, State},
+        http::HeaderMap,
+        routing::{get, post},
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc;
+
+    pub(crate) const API_KEY: &str = "syncthing-test-key";
+    /// Device ID the fake Syncthing runs as
+    pub(crate) const LOCAL_DEVICE: &str = "LOCAL-DEVICE";
+    /// Device the fake Syncthing reports as only halfway through every folder
+    pub(crate) const PARTIAL_DEVICE: &str = "PARTIAL-DEVICE";
+    /// Device the fake Syncthing fails to report completion for
+    pub(crate) const FAILING_DEVICE: &str = "FAILING-DEVICE";
+
+    type FolderQuery = Query<HashMap<String, String>>;
+
+    /// A fake Syncthing keeping ignore patterns in memory and reporting every scan request
+    pub(crate) struct MockSyncthing {
+        pub config: SyncthingConfig,
+        pub scans: mpsc::UnboundedReceiver<String>,
+        pub ignores: Arc<Mutex<HashMap<String, Vec<String>>>>,
+        pub folders: Arc<Mutex<Vec<FolderConfig>>>,
+        /// Number of status requests served
+        pub status_requests: Arc<AtomicUsize>,
+        /// Devices holding each file, keyed by path within the folder
+        pub availability: Arc<Mutex<HashMap<String, Vec<String>>>>,
+        /// Locally changed items of every folder, cleared by a revert
+        pub local_changes: Arc<Mutex<HashMap<String, Vec<LocalChange>>>>,
+    }
+
+    #[derive(Clone)]
+    struct MockState {
+        scans: mpsc::UnboundedSender<String>,
+        ignores: Arc<Mutex<HashMap<String, Vec<String>>>>,
+        folders: Arc<Mutex<Vec<FolderConfig>>>,
+        status_requests: Arc<AtomicUsize>,
+        availability: Arc<Mutex<HashMap<String, Vec<String>>>>,
+        local_changes: Arc<Mutex<HashMap<String, Vec<LocalChange>>>>,
+    }
+
+    /// Serves every change on every page, like a Syncthing ignoring the paging parameters
+    async fn local_changed(
+        State(state): State<MockState>,
+        query: FolderQuery,
+    ) -> Json<serde_json::Value> {
+        let local_changes = state.local_changes.lock().unwrap();
+        let files = local_changes.get(&folder(&query)).cloned();
+        Json(serde_json::json!({ "files": files.unwrap_or_default() }))
+    }
+
+    async fn revert(State(state): State<MockState>, query: FolderQuery) -> axum::http::StatusCode {
+        state.local_changes.lock().unwrap().remove(&folder(&query));
+        axum::http::StatusCode::OK
+    }
+
+    async fn system_status() -> Json<serde_json::Value> {
+        Json(serde_json::json!({ "myID": LOCAL_DEVICE }))
+    }
+
+    async fn file(
+        State(state): State<MockState>,
+        query: FolderQuery,
+    ) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+        let file = query.get("file").cloned().unwrap_or_default();
+        let availability = state.availability.lock().unwrap();
+        let devices = availability
+            .get(&file)
+            .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+        let devices: Vec<_> = devices
+            .iter()
+            .map(|id| serde_json::json!({ "id": id, "fromTemporary": false }))
+            .collect();
+        Ok(Json(serde_json::json!({ "availability": devices })))
+    }
+
+    async fn completion(
+        query: FolderQuery,
+    ) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+        if query.get("device").is_some_and(|id| id == FAILING_DEVICE) {
+            return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        let partial = query.get("device").is_some_and(|id| id == PARTIAL_DEVICE);
+        Ok(Json(serde_json::json!({
+            "completion": if partial { 50.0 } else { 100.0 },
+            "needBytes": if partial { 2048 } else { 0 },
+        })))
+    }
+
+    /// Every folder is reported as syncing, with 1024 bytes still needed
+    async fn status(State(state): State<MockState>) -> Json<serde_json::Value> {
+        state.status_requests.fetch_add(1, Ordering::SeqCst);
+        Json(serde_json::json!({
+            "state": "syncing",
+            "stateChanged": "2025-01-01T00:00:00Z",
+            "globalBytes": 4096,
+            "localBytes": 3072,
+            "inSyncBytes": 3072,
+            "needBytes": 1024,
+            "needFiles": 1,
+            "needDeletes": 0,
+            "sequence": 42,
+        }))
+    }
+
+    async fn folders(State(state): State<MockState>) -> Json<Vec<FolderConfig>> {
+        Json(state.folders.lock().unwrap().clone())
+    }
+
+    fn folder(query: &FolderQuery) -> String {
+        query.get("folder").cloned().unwrap_or_default()
+    }
+
+    async fn scan(State(state): State<MockState>, query: FolderQuery) -> axum::http::StatusCode {
+        let _ = state.scans.send(folder(&query));
+        axum::http::StatusCode::OK
+    }
+
+    async fn get_ignores(State(state): State<MockState>, query: FolderQuery) -> Json<Ignores> {
+        let ignores = state.ignores.lock().unwrap();
+        Json(Ignores {
+            ignore: ignores.get(&folder(&query)).cloned(),
+        })
+    }
+
+    async fn set_ignores(
+        State(state): State<MockState>,
+        query: FolderQuery,
+        Json(body): Json<Ignores>,
+    ) -> axum::http::StatusCode {
+        let mut ignores = state.ignores.lock().unwrap();
+        ignores.insert(folder(&query), body.ignore.unwrap_or_default());
+        axum::http::StatusCode::OK
+    }
+
+    async fn check_api_key(
+        headers: HeaderMap,
+        request: axum::extract::Request,
+        next: axum::middleware::Next,
+    ) -> axum::response::Response {
+        use axum::response::IntoResponse;
+        match headers.get("X-API-Key") {
+            Some(key) if key == API_KEY => next.run(request).await,
+            _ => axum::http::StatusCode::FORBIDDEN.into_response(),
+        }
+    }
+
+    pub(crate) async fn mock_syncthing() -> MockSyncthing {
+        let (sender, scans) = mpsc::unbounded_channel();
+        let ignores = Arc::new(Mutex::new(HashMap::new()));
+        let folders = Arc::new(Mutex::new(vec![]));
+        let status_requests = Arc::new(AtomicUsize::new(0));
+        let availability = Arc::new(Mutex::new(HashMap::new()));
+        let local_changes = Arc::new(Mutex::new(HashMap::new()));
+
+        let app = Router::new()
+            .route("/rest/config/folders", get(self::folders))
+            .route("/rest/db/scan", post(scan))
+            .route("/rest/db/status", get(status))
+            .route("/rest/db/file", get(file))
+            .route("/rest/db/completion", get(completion))
+            .route("/rest/db/localchanged", get(local_changed))
+            .route("/rest/db/revert", post(revert))
+            .route("/rest/system/status", get(system_status))
+            .route("/rest/db/ignores", get(get_ignores).post(set_ignores))
+            .layer(axum::middleware::from_fn(check_api_key))
+            .with_state(MockState {
+                scans: sender,
+                ignores: ignores.clone(),
+                folders: folders.clone(),
+                status_requests: status_requests.clone(),
+                availability: availability.clone(),
+                local_changes: local_changes.clone(),
+            });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        MockSyncthing {
+            config: SyncthingConfig {
+                url: format!("http://{}/", addr),
+                api_key: API_KEY.to_string(),
+                discover: None,
+                status_ttl_secs: None,
+            },
+            scans,
+            ignores,
+            folders,
+            status_requests,
+            availability,
+            local_changes,
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_requests_folder_rescan() {
+        let mut mock = mock_syncthing().await;
+
+        let client = Client::new(&mock.config);
+        client.scan("movies folder").await.unwrap();
+        assert_eq!(mock.scans.recv().await.unwrap(), "movies folder");
+
+        let client = Client::new(&SyncthingConfig {
+            api_key: "wrong".to_string(),
+            ..mock.config
+        });
+        assert!(matches!(
+            client.scan("movies").await,
+            Err(SyncthingError(HttpError::Status(
+                hyper::StatusCode::FORBIDDEN
+            )))
+        ));
+    }
+
+    #[tokio::test]
+    async fn scan_rejects_unsupported_urls() {
+        let client = Client::new(&SyncthingConfig {
+            url: "https://localhost:8384".to_string(),
+            api_key: API_KEY.to_string(),
+            discover: None,
+            status_ttl_secs: None,
+        });
+        assert!(matches!(
+            client.scan("movies").await,
+            Err(SyncthingError(HttpError::InvalidUrl(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn folders_lists_configured_folders() {
+        let mock = mock_syncthing().await;
+        mock.folders.lock().unwrap().push(FolderConfig {
+            id: "abcde-12345".to_string(),
+            label: "Movies".to_string(),
+            path: "/srv/movies".to_string(),
+            folder_type: FolderType::SendReceive,
+        });
+
+        let folders = Client::new(&mock.config).folders().await.unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].id, "abcde-12345");
+        assert_eq!(folders[0].path, "/srv/movies");
+    }
+
+    #[tokio::test]
+    async fn folder_status_is_cached_until_ttl_expires() {
+        let mock = mock_syncthing().await;
+        let client = Client::new(&mock.config);
+        let cache = FolderStatusCache::default();
+
+        let status = cache
+            .get(&client, "movies", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(status.state, "syncing");
+        assert_eq!(status.need_bytes, 1024);
+
+        cache
+            .get(&client, "movies", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(mock.status_requests.load(Ordering::SeqCst), 1);
+
+        cache.get(&client, "movies", Duration::ZERO).await.unwrap();
+        assert_eq!(mock.status_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn ignores_round_trip() {
+        let mock = mock_syncthing().await;
+        let client = Client::new(&mock.config);
+
+        // Folders without patterns report null rather than an empty list
+        assert!(client.ignores("movies").await.unwrap().is_empty());
+
+        let lines = vec!["/Movie 1 (2023)".to_string(), "(?i)/movie 2".to_string()];
+        client.set_ignores("movies", lines.clone()).await.unwrap();
+        assert_eq!(client.ignores("movies").await.unwrap(), lines);
+        assert_eq!(mock.ignores.lock().unwrap()["movies"], lines);
+    }
+
+    #[tokio::test]
+    async fn local_changes_stop_when_pages_repeat() {
+        let mock = mock_syncthing().await;
+        let changes: Vec<_> = (0..LOCAL_CHANGES_PER_PAGE)
+            .map(|i| LocalChange {
+                name: format!("Movie {}", i),
+                size: 0,
+                deleted: true,
+                modified: None,
+            })
+            .collect();
+        mock.local_changes
+            .lock()
+            .unwrap()
+            .insert("movies".to_string(), changes);
+
+        // The mock serves the same full page forever
+        let client = Client::new(&mock.config);
+        let listed = client.local_changes("movies").await.unwrap();
+        assert_eq!(listed.len(), LOCAL_CHANGES_PER_PAGE);
+    }
+}
@@ -1,13 +1,26 @@
+use crate::cleanup;
 use crate::config;
+use crate::diff;
+use crate::error::{ApiError, ApiJson, ErrorCode};
 use crate::filesystem;
+use crate::hashing;
+use crate::ignores::{IgnoreStore, Ignores};
 use crate::models::*;
+use crate::remote::AgentClient;
 use axum::{
     Json,
+    body::Body,
     extract::{Path, State},
-    http::StatusCode,
+    http::{StatusCode, header},
     response::{Html, IntoResponse, Response},
 };
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::path::PathBuf;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Most Syncthing file lookups in flight for one availability request
+const MAX_AVAILABILITY_LOOKUPS: usize = 8;
 
 /// Helper function to build the category base path
 fn build_category_base_path(
@@ -17,6 +30,115 @@ fn build_category_base_path(
     std::path::Path::new(&agent_config.base_path).join(&category.relative_path)
 }
 
+/// Helper function to find a category by ID
+fn find_category<'a>(
+    data: &'a config::Data,
+    category_id: &str,
+) -> Result<&'a config::Category, ApiError> {
+    data.categories
+        .iter()
+        .find(|c| c.id == category_id)
+        .ok_or_else(|| ApiError::category_not_found(category_id))
+}
+
+/// Helper function to scan a whole category without holding up the async workers
+async fn scan_category(
+    data: &config::Data,
+    category: &config::Category,
+) -> Result<filesystem::ItemGroup, ApiError> {
+    let category_path = build_category_base_path(&data.agent, category);
+    let (id, name) = (category.id.clone(), category.name.clone());
+    let symlinks = data.agent.symlinks;
+
+    tokio::task::spawn_blocking(move || filesystem::scan_items(&category_path, id, name, symlinks))
+        .await
+        .map_err(|err| {
+            tracing::error!("Scanning category failed: {}", err);
+            ApiError::new(ErrorCode::Internal, "Scanning category failed")
+        })?
+        .map_err(|error| ApiError::scan(&category.name, error))
+}
+
+/// Helper function to reject empty folder paths and components that could leave the category
+/// or, through line breaks, add lines of their own to .stignore
+fn validate_folder_path<S: AsRef<OsStr>>(folder_path: &[S]) -> Result<(), ApiError> {
+    if folder_path.is_empty() {
+        return Err(ApiError::empty_path());
+    }
+
+    if let Some(component) = folder_path
+        .iter()
+        .find(|component| !filesystem::is_plain_name(component.as_ref()))
+    {
+        return Err(ApiError::new(
+            ErrorCode::PathOutsideCategory,
+            format!(
+                "Path component {:?} isn't a name within the category",
+                component.as_ref()
+            ),
+        ));
+    }
+
+    match folder_path.iter().find(|component| {
+        component
+            .as_ref()
+            .to_string_lossy()
+            .chars()
+            .any(char::is_control)
+    }) {
+        Some(component) => Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!(
+                "Path component {:?} contains control characters",
+                component.as_ref()
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Helper function to decode requested path components into filesystem names
+fn decode_folder_path(
+    folder_path: &[String],
+    raw_components: &[usize],
+) -> Result<Vec<OsString>, ApiError> {
+    filesystem::decode_path_components(folder_path, raw_components).ok_or_else(|| {
+        ApiError::new(
+            ErrorCode::InvalidRawName,
+            "Folder path contains an invalid raw name",
+        )
+    })
+}
+
+/// Helper function to turn decoded path components back into the names used by ignore rules.
+/// Ignore rules are text, so names that aren't valid UTF-8 are written lossily.
+fn lossy_folder_path(folder_path: &[OsString]) -> Vec<String> {
+    folder_path
+        .iter()
+        .map(|component| component.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Helper function to read the ignore patterns of a category
+async fn ignore_patterns(
+    data: &config::Data,
+    category: &config::Category,
+) -> Result<Vec<String>, ApiError> {
+    Ok(Ignores::for_category(data, category)?.patterns().await?)
+}
+
+/// Helper function to have Syncthing apply a change to a category without waiting for its next scan
+fn request_rescan(data: &config::Data, category: &config::Category) {
+    if let Some(client) = data.syncthing_client() {
+        let folder_id = category.folder_id().to_string();
+        tokio::spawn(async move {
+            if let Err(err) = client.scan(&folder_id).await {
+                tracing::warn!("Unable to rescan Syncthing folder '{}': {}", folder_id, err);
+            }
+        });
+    }
+}
+
 pub async fn help() -> Html<&'static str> {
     Html(
         "Please visit <a href='https://github.com/dalmura/stignore-agent'>the documentation</a> for further information",
@@ -25,25 +147,73 @@ pub async fn help() -> Html<&'static str> {
 
 // GET categories
 // Returns all configured categories that the agent is configured for!
-pub async fn category_list(State(data): State<config::Data>) -> impl IntoResponse {
-    let items = data
+pub async fn category_list(
+    State(data): State<config::Data>,
+) -> Result<Json<CategoryListingResponse>, ApiError> {
+    let categories: Vec<(String, String, PathBuf)> = data
         .categories
         .iter()
         .map(|c| {
-            let category_path = build_category_base_path(&data.agent, c);
-            let children = filesystem::build_items(&category_path, false);
-
-            filesystem::ItemGroup {
-                id: c.id.clone(),
-                name: c.name.clone(),
-                size_kb: children.iter().map(|c| c.size_kb).sum(),
-                items: children,
-                leaf: false,
-            }
+            (
+                c.id.clone(),
+                c.name.clone(),
+                build_category_base_path(&data.agent, c),
+            )
         })
         .collect();
 
-    (StatusCode::OK, Json(CategoryListingResponse { items }))
+    let symlinks = data.agent.symlinks;
+    let items = tokio::task::spawn_blocking(move || {
+        categories
+            .into_iter()
+            .map(|(id, name, category_path)| {
+                // An unreadable category is still listed, carrying the error instead of items
+                filesystem::scan_items(&category_path, id.clone(), name.clone(), symlinks)
+                    .unwrap_or_else(|err| {
+                        let mut item = filesystem::group_items(id, name, vec![]);
+                        item.errors.push(err);
+                        item
+                    })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Scanning categories failed: {}", err);
+        ApiError::new(ErrorCode::Internal, "Scanning categories failed")
+    })?;
+
+    let sync = sync_states(&data).await;
+
+    Ok(Json(CategoryListingResponse { items, sync }))
+}
+
+/// Helper function to look up the Syncthing folder status of every category
+async fn sync_states(data: &config::Data) -> Vec<CategorySyncState> {
+    let (Some(syncthing), Some(client)) = (&data.syncthing, data.syncthing_client()) else {
+        return vec![];
+    };
+    let ttl = std::time::Duration::from_secs(syncthing.status_ttl_secs.unwrap_or(10));
+
+    let mut states = vec![];
+    for category in &data.categories {
+        let status = data
+            .folder_status_cache
+            .get(&client, category.folder_id(), ttl)
+            .await;
+        let (status, error) = match status {
+            Ok(status) => (Some(status), None),
+            Err(err) => (None, Some(err)),
+        };
+
+        states.push(CategorySyncState {
+            category_id: category.id.clone(),
+            folder_id: category.folder_id().to_string(),
+            status,
+            error,
+        });
+    }
+    states
 }
 
 // GET category info
@@ -51,28 +221,72 @@ pub async fn category_list(State(data): State<config::Data>) -> impl IntoRespons
 pub async fn category_info(
     State(data): State<config::Data>,
     Path(category_id): Path<String>,
-) -> Response {
-    match data.categories.iter().find(|x| x.id == category_id) {
-        Some(category) => {
-            let category_path = build_category_base_path(&data.agent, category);
+) -> Result<Json<CategoryInfoResponse>, ApiError> {
+    let category = find_category(&data, &category_id)?;
+    let item = scan_category(&data, category).await?;
+
+    Ok(Json(CategoryInfoResponse {
+        name: category.name.clone(),
+        items: item.items,
+        errors: item.errors,
+    }))
+}
 
-            (
-                StatusCode::OK,
-                Json(CategoryInfoResponse {
-                    name: category.name.clone(),
-                    items: filesystem::build_items(&category_path, false),
-                }),
-            )
-                .into_response()
-        }
-        None => (
-            StatusCode::NOT_FOUND,
-            Json(NotFoundResponse {
-                message: format!("Category ID {} not found", category_id),
-            }),
-        )
-            .into_response(),
+/// Helper function to find a receive-only category along with a Syncthing client
+fn receive_only_category<'a>(
+    data: &'a config::Data,
+    category_id: &str,
+) -> Result<(&'a config::Category, crate::syncthing::Client), ApiError> {
+    let category = find_category(data, category_id)?;
+
+    if category.folder_type.sends_changes() {
+        return Err(ApiError::new(
+            ErrorCode::NotReceiveOnly,
+            format!("Category '{}' isn't a receive-only folder", category.name),
+        ));
     }
+
+    let client = data.syncthing_client().ok_or_else(|| {
+        ApiError::new(
+            ErrorCode::SyncthingNotConfigured,
+            "Syncthing isn't configured",
+        )
+    })?;
+
+    Ok((category, client))
+}
+
+// GET category local changes
+// Lists the items changed locally in a receive-only category, such as deletions
+pub async fn category_local_changes(
+    State(data): State<config::Data>,
+    Path(category_id): Path<String>,
+) -> Result<Json<LocalChangesResponse>, ApiError> {
+    let (category, client) = receive_only_category(&data, &category_id)?;
+
+    let items = client.local_changes(category.folder_id()).await?;
+
+    Ok(Json(LocalChangesResponse {
+        category_id: category.id.clone(),
+        folder_id: category.folder_id().to_string(),
+        items,
+    }))
+}
+
+// POST category revert
+// Has Syncthing undo the local changes of a receive-only category, restoring deleted items
+pub async fn post_category_revert(
+    State(data): State<config::Data>,
+    Path(category_id): Path<String>,
+) -> Result<Json<RevertResponse>, ApiError> {
+    let (category, client) = receive_only_category(&data, &category_id)?;
+
+    tracing::info!("Reverting local changes in category '{}'", category.id);
+    client.revert(category.folder_id()).await?;
+
+    Ok(Json(RevertResponse {
+        message: format!("Reverted local changes in category '{}'", category.name),
+    }))
 }
 
 // POST itemgroup info
@@ -80,67 +294,63 @@ pub async fn category_info(
 // We must be given a series of correct itemgroup names to traverse
 pub async fn post_item_info(
     State(data): State<config::Data>,
-    Json(payload): Json<ItemInfoRequest>,
-) -> Response {
-    let item_path: Vec<&str> = payload.item_path.iter().map(AsRef::as_ref).collect();
-
+    ApiJson(payload): ApiJson<ItemInfoRequest>,
+) -> Result<Json<ItemInfoResponse>, ApiError> {
     // Validate that the first item in the path corresponds to a valid category
-    if item_path.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(NotFoundResponse {
-                message: "Item path cannot be empty".to_string(),
-            }),
-        )
-            .into_response();
-    }
-
-    let category_id = &item_path[0];
-    let category = match data.categories.iter().find(|c| c.id == *category_id) {
-        Some(cat) => cat,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(NotFoundResponse {
-                    message: format!("Category ID '{}' not found", category_id),
-                }),
-            )
-                .into_response();
-        }
+    let Some((category_id, item_path)) = payload.item_path.split_first() else {
+        return Err(ApiError::new(
+            ErrorCode::EmptyPath,
+            "Item path cannot be empty",
+        ));
     };
-
+    let category = find_category(&data, category_id)?;
     let category_path = build_category_base_path(&data.agent, category);
 
-    if item_path.len() == 1 {
+    if item_path.is_empty() {
         // Return the category itself
-        let items = filesystem::build_items(&category_path, false);
-        let category_item = filesystem::ItemGroup {
-            id: category.id.clone(),
-            name: category.name.clone(),
-            size_kb: items.iter().map(|c| c.size_kb).sum(),
-            items,
-            leaf: false,
-        };
-        return (
-            StatusCode::OK,
-            Json(ItemInfoResponse {
-                item: category_item,
-            }),
-        )
-            .into_response();
+        let item = scan_category(&data, category).await?;
+        return Ok(Json(ItemInfoResponse { item }));
     }
 
     // Navigate to the specific item within the category
-    let item_path_within_category = &item_path[1..];
-    match filesystem::get_item(&category_path, item_path_within_category) {
-        Some(item) => (StatusCode::OK, Json(ItemInfoResponse { item })).into_response(),
-        None => (
-            StatusCode::NOT_FOUND,
-            Json(NotFoundResponse {
-                message: format!("Item Path '{:?}' not found", &item_path),
-            }),
+    // Raw component indexes count the category ID, which is never raw
+    let raw_components = payload
+        .raw_components
+        .iter()
+        .map(|index| index.checked_sub(1))
+        .collect::<Option<Vec<usize>>>()
+        .ok_or_else(|| {
+            ApiError::new(
+                ErrorCode::InvalidRawName,
+                "The category ID can't be a raw name",
+            )
+        })?;
+    let item_path_within_category = decode_folder_path(item_path, &raw_components)?;
+    validate_folder_path(&item_path_within_category)?;
+
+    let symlinks = data.agent.symlinks;
+    let case_insensitive = category.case_insensitive;
+    let found = tokio::task::spawn_blocking(move || {
+        filesystem::get_item(
+            &category_path,
+            &item_path_within_category,
+            symlinks,
+            case_insensitive,
         )
-            .into_response(),
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Scanning item failed: {}", err);
+        ApiError::new(ErrorCode::Internal, "Scanning item failed")
+    })?;
+
+    match found {
+        Ok(Some(item)) => Ok(Json(ItemInfoResponse { item })),
+        Ok(None) => Err(ApiError::new(
+            ErrorCode::ItemNotFound,
+            format!("Item Path '{:?}' not found", &payload.item_path),
+        )),
+        Err(error) => Err(ApiError::scan(&category.name, error)),
     }
 }
 
@@ -148,77 +358,38 @@ pub async fn post_item_info(
 // Adds a folder path to .stignore in the appropriate category
 pub async fn post_ignore(
     State(data): State<config::Data>,
-    Json(payload): Json<IgnoreRequest>,
-) -> Response {
+    ApiJson(payload): ApiJson<IgnoreRequest>,
+) -> Result<Json<IgnoreResponse>, ApiError> {
     tracing::info!(
         "Processing ignore request for category: '{}', folder_path: {:?}",
         payload.category_id,
         payload.folder_path
     );
 
-    // Validate folder path is not empty
-    if payload.folder_path.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(IgnoreResponse {
-                success: false,
-                message: "Folder path cannot be empty".to_string(),
-                ignored_path: None,
-            }),
-        )
-            .into_response();
-    }
-
-    // Find the category by matching the category ID
-    let category = match data.categories.iter().find(|c| c.id == payload.category_id) {
-        Some(cat) => cat,
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(IgnoreResponse {
-                    success: false,
-                    message: format!("Category ID '{}' not found", payload.category_id),
-                    ignored_path: None,
-                }),
-            )
-                .into_response();
-        }
-    };
-
-    let category_base_path = build_category_base_path(&data.agent, category);
+    let folder_path = decode_folder_path(&payload.folder_path, &payload.raw_components)?;
+    validate_folder_path(&folder_path)?;
+    let category = find_category(&data, &payload.category_id)?;
+    let ignores = Ignores::for_category(&data, category)?;
 
-    // Add to .stignore using the folder path components directly
-    match filesystem::add_to_stignore(&category_base_path, &payload.folder_path, &category.name) {
+    // Add the rule using the folder path components directly
+    match ignores.add(&lossy_folder_path(&folder_path)).await {
         filesystem::StignoreResult::Success {
             ignored_path,
             message,
-        } => (
-            StatusCode::OK,
-            Json(IgnoreResponse {
+        } => {
+            request_rescan(&data, category);
+            Ok(Json(IgnoreResponse {
                 success: true,
                 message,
                 ignored_path: Some(ignored_path),
-            }),
-        )
-            .into_response(),
-        filesystem::StignoreResult::AlreadyIgnored { ignored_path } => (
-            StatusCode::OK,
-            Json(IgnoreResponse {
-                success: true,
-                message: "Path is already ignored".to_string(),
-                ignored_path: Some(ignored_path),
-            }),
-        )
-            .into_response(),
-        filesystem::StignoreResult::Error { message } => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(IgnoreResponse {
-                success: false,
-                message,
-                ignored_path: None,
-            }),
-        )
-            .into_response(),
+            }))
+        }
+        filesystem::StignoreResult::AlreadyIgnored { ignored_path } => Ok(Json(IgnoreResponse {
+            success: true,
+            message: "Path is already ignored".to_string(),
+            ignored_path: Some(ignored_path),
+        })),
+        filesystem::StignoreResult::Error { code, message } => Err(ApiError::new(code, message)),
     }
 }
 
@@ -226,160 +397,640 @@ pub async fn post_ignore(
 // Checks if a folder is ignored in .stignore
 pub async fn post_ignore_status(
     State(data): State<config::Data>,
-    Json(payload): Json<IgnoreStatusRequest>,
-) -> Response {
-    // Validate folder path is not empty
-    if payload.folder_path.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(IgnoreStatusResponse { ignored: false }),
-        )
-            .into_response();
-    }
-
-    // Find the category by matching the category ID
-    let category = match data.categories.iter().find(|c| c.id == payload.category_id) {
-        Some(cat) => cat,
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(IgnoreStatusResponse { ignored: false }),
-            )
-                .into_response();
-        }
-    };
-
-    let category_base_path = build_category_base_path(&data.agent, category);
+    ApiJson(payload): ApiJson<IgnoreStatusRequest>,
+) -> Result<Json<IgnoreStatusResponse>, ApiError> {
+    let folder_path = decode_folder_path(&payload.folder_path, &payload.raw_components)?;
+    validate_folder_path(&folder_path)?;
+    let category = find_category(&data, &payload.category_id)?;
 
     // Check if the folder path is ignored
-    let ignored = filesystem::is_path_ignored(&category_base_path, &payload.folder_path);
+    let ignored = Ignores::for_category(&data, category)?
+        .is_ignored(&lossy_folder_path(&folder_path))
+        .await
+        .inspect_err(|err| {
+            tracing::warn!(
+                "Unable to check ignores for category '{}': {}",
+                category.id,
+                err
+            )
+        })?;
 
-    (StatusCode::OK, Json(IgnoreStatusResponse { ignored })).into_response()
+    Ok(Json(IgnoreStatusResponse { ignored }))
 }
 
 // POST ignore-status-bulk
 // Checks ignore status for multiple folders at once
 pub async fn post_ignore_status_bulk(
     State(data): State<config::Data>,
-    Json(payload): Json<BulkIgnoreStatusRequest>,
-) -> Response {
+    ApiJson(payload): ApiJson<BulkIgnoreStatusRequest>,
+) -> Json<BulkIgnoreStatusResponse> {
+    // Each category's patterns are only read once, however many of its paths are checked
+    let mut patterns = HashMap::new();
     let mut results = Vec::new();
 
     for item in payload.items {
-        // Use the same logic as the single ignore status check
-        let ignored = if item.folder_path.is_empty() {
-            false
-        } else {
-            // Find the category by matching the category ID
-            match data.categories.iter().find(|c| c.id == item.category_id) {
-                Some(category) => {
-                    let category_base_path = build_category_base_path(&data.agent, category);
-
-                    // Check if the folder path is ignored
-                    filesystem::is_path_ignored(&category_base_path, &item.folder_path)
-                }
-                None => false, // Invalid category
-            }
+        let (matched_rule, error) = match matched_ignore_rule(&data, &item, &mut patterns).await {
+            Ok(matched_rule) => (matched_rule, None),
+            Err(err) => (None, Some(err)),
         };
 
         results.push(BulkIgnoreStatusItem {
             category_id: item.category_id,
             folder_path: item.folder_path,
-            ignored,
+            raw_components: item.raw_components,
+            ignored: matched_rule.is_some(),
+            matched_rule,
+            error,
         });
     }
 
-    (
-        StatusCode::OK,
-        Json(BulkIgnoreStatusResponse { items: results }),
+    Json(BulkIgnoreStatusResponse { items: results })
+}
+
+/// Helper function to find the ignore rule covering one item of a bulk status check
+async fn matched_ignore_rule(
+    data: &config::Data,
+    item: &IgnoreStatusRequest,
+    patterns: &mut HashMap<String, Result<Vec<String>, ApiError>>,
+) -> Result<Option<String>, ApiError> {
+    let folder_path = decode_folder_path(&item.folder_path, &item.raw_components)?;
+    validate_folder_path(&folder_path)?;
+    let category = find_category(data, &item.category_id)?;
+
+    if !patterns.contains_key(&category.id) {
+        let category_patterns = ignore_patterns(data, category).await;
+        if let Err(err) = &category_patterns {
+            tracing::warn!(
+                "Unable to check ignores for category '{}': {}",
+                category.id,
+                err.message
+            );
+        }
+        patterns.insert(category.id.clone(), category_patterns);
+    }
+
+    let category_patterns = patterns[&category.id].as_ref().map_err(Clone::clone)?;
+    Ok(crate::ignores::matching_rule(
+        category_patterns,
+        &lossy_folder_path(&folder_path),
+        category.case_insensitive,
     )
-        .into_response()
+    .map(str::to_string))
 }
 
 // POST delete
 // Deletes a folder path from the filesystem
 pub async fn post_delete(
     State(data): State<config::Data>,
-    Json(payload): Json<DeleteRequest>,
-) -> Response {
+    ApiJson(payload): ApiJson<DeleteRequest>,
+) -> Result<Json<DeleteResponse>, ApiError> {
     tracing::info!(
         "Processing delete request for category: '{}', folder_path: {:?}",
         payload.category_id,
         payload.folder_path
     );
 
-    // Validate folder path is not empty
     if payload.folder_path.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(DeleteResponse {
-                success: false,
-                message: "Folder path cannot be empty".to_string(),
-                deleted_path: None,
-            }),
-        )
-            .into_response();
-    }
-
-    // Find the category by matching the category ID
-    let category = match data.categories.iter().find(|c| c.id == payload.category_id) {
-        Some(cat) => cat,
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(DeleteResponse {
-                    success: false,
-                    message: format!("Category ID '{}' not found", payload.category_id),
-                    deleted_path: None,
-                }),
-            )
-                .into_response();
-        }
-    };
+        return Err(ApiError::empty_path());
+    }
+    let category = find_category(&data, &payload.category_id)?;
+    let folder_path = decode_folder_path(&payload.folder_path, &payload.raw_components)?;
+    validate_folder_path(&folder_path)?;
 
     let category_base_path = build_category_base_path(&data.agent, category);
 
+    // A missing path is reported as such, whether or not it would pass the guard
+    match filesystem::resolve_path(
+        &category_base_path,
+        &folder_path,
+        data.agent.symlinks,
+        category.case_insensitive,
+    ) {
+        Ok(Some(path)) if path.exists() => {}
+        Ok(_) => {
+            return Err(ApiError::new(
+                ErrorCode::ItemNotFound,
+                format!(
+                    "Path '{}' not found",
+                    filesystem::build_unix_path_string(&folder_path)
+                ),
+            ));
+        }
+        Err(error) => return Err(ApiError::scan(&category.name, error)),
+    }
+
+    // A path that isn't ignored would be deleted on every peer of a folder that sends changes
+    if data.agent.delete_guard && category.folder_type.sends_changes() {
+        if payload.force {
+            tracing::warn!(
+                "Forcing delete of {:?} in category '{}' without checking ignores",
+                payload.folder_path,
+                category.id
+            );
+        } else {
+            let ignored = Ignores::for_category(&data, category)?
+                .is_ignored(&lossy_folder_path(&folder_path))
+                .await?;
+
+            if !ignored {
+                return Err(ApiError::new(
+                    ErrorCode::NotIgnored,
+                    format!(
+                        "Refusing to delete '{}': it isn't ignored, so Syncthing would delete it on every device sharing category '{}'. Ignore it first or set force",
+                        filesystem::build_unix_path_string(&folder_path),
+                        category.name
+                    ),
+                ));
+            }
+        }
+    }
+
     // Delete from filesystem
     match filesystem::delete_from_filesystem(
         &category_base_path,
-        &payload.folder_path,
+        &folder_path,
         &category.name,
+        data.agent.symlinks,
+        category.case_insensitive,
     ) {
         filesystem::DeleteResult::Success {
             deleted_path,
             message,
-        } => (
-            StatusCode::OK,
-            Json(DeleteResponse {
+        } => {
+            request_rescan(&data, category);
+            Ok(Json(DeleteResponse {
                 success: true,
                 message,
                 deleted_path: Some(deleted_path),
-            }),
+            }))
+        }
+        filesystem::DeleteResult::NotFound { requested_path } => Err(ApiError::new(
+            ErrorCode::ItemNotFound,
+            format!("Path '{}' not found", requested_path),
+        )),
+        filesystem::DeleteResult::Error { message } => {
+            Err(ApiError::new(ErrorCode::IoError, message))
+        }
+    }
+}
+
+// POST hashes
+// Streams the SHA-256 of every file under a folder as newline-delimited JSON
+pub async fn post_hashes(
+    State(data): State<config::Data>,
+    ApiJson(payload): ApiJson<HashRequest>,
+) -> Result<Response, ApiError> {
+    let category = find_category(&data, &payload.category_id)?;
+    let folder_path = decode_folder_path(&payload.folder_path, &payload.raw_components)?;
+    if !folder_path.is_empty() {
+        validate_folder_path(&folder_path)?;
+    }
+
+    // List every file up front, so a missing or unreadable path fails the request
+    let category_base_path = build_category_base_path(&data.agent, category);
+    let symlinks = data.agent.symlinks;
+    let case_insensitive = category.case_insensitive;
+    let files = tokio::task::spawn_blocking(move || {
+        hashing::collect_files(
+            &category_base_path,
+            &folder_path,
+            symlinks,
+            case_insensitive,
         )
-            .into_response(),
-        filesystem::DeleteResult::NotFound { requested_path } => (
-            StatusCode::NOT_FOUND,
-            Json(DeleteResponse {
-                success: false,
-                message: format!("Path '{}' not found", requested_path),
-                deleted_path: None,
-            }),
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Listing files to hash failed: {}", err);
+        ApiError::new(ErrorCode::Internal, "Listing files to hash failed")
+    })?
+    .map_err(|error| ApiError::scan(&category.name, error))?
+    .ok_or_else(|| {
+        ApiError::new(
+            ErrorCode::ItemNotFound,
+            format!("Folder path '{:?}' not found", payload.folder_path),
         )
-            .into_response(),
-        filesystem::DeleteResult::Error { message } => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(DeleteResponse {
-                success: false,
-                message,
-                deleted_path: None,
-            }),
+    })?;
+
+    let cache = match payload.skip_cache {
+        true => None,
+        false => Some(data.hash_cache.clone()),
+    };
+
+    // Hash on a blocking thread, sending each result as soon as it's ready
+    let (sender, receiver) = tokio::sync::mpsc::channel::<Result<String, std::io::Error>>(16);
+    tokio::task::spawn_blocking(move || {
+        for file in files {
+            let (sha256, error) = match hashing::hash_file(&file, cache.as_ref()) {
+                Ok(sha256) => (Some(sha256), None),
+                Err(err) => (None, Some(err.to_string())),
+            };
+
+            let line = FileHashResponse {
+                path: file.components,
+                raw_components: file.raw_components,
+                size: file.size,
+                sha256,
+                error,
+            };
+
+            let mut line = serde_json::to_string(&line).map_err(std::io::Error::other);
+            if let Ok(line) = line.as_mut() {
+                line.push('\n');
+            }
+
+            // Stop hashing once the client has gone away
+            if sender.blocking_send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response())
+}
+
+// GET disk usage
+// Returns the space and inode usage of the filesystem under each category
+pub async fn disk_usage(State(data): State<config::Data>) -> impl IntoResponse {
+    let categories = data
+        .categories
+        .iter()
+        .map(|c| {
+            let category_path = build_category_base_path(&data.agent, c);
+
+            match filesystem::disk_usage(&category_path) {
+                Ok(usage) => CategoryDiskUsage {
+                    category_id: c.id.clone(),
+                    usage: Some(usage),
+                    error: None,
+                },
+                Err(err) => CategoryDiskUsage {
+                    category_id: c.id.clone(),
+                    usage: None,
+                    error: Some(err),
+                },
+            }
+        })
+        .collect();
+
+    (StatusCode::OK, Json(DiskUsageResponse { categories }))
+}
+
+// POST cleanup plan
+// Proposes items to ignore and delete until the category's filesystem has the requested free space
+pub async fn post_cleanup_plan(
+    State(data): State<config::Data>,
+    ApiJson(payload): ApiJson<CleanupPlanRequest>,
+) -> Result<Json<CleanupPlanResponse>, ApiError> {
+    let category = find_category(&data, &payload.category_id)?;
+    let category_path = build_category_base_path(&data.agent, category);
+
+    let usage = filesystem::disk_usage(&category_path)
+        .map_err(|error| ApiError::scan(&category.name, error))?;
+
+    let scanned = scan_category(&data, category).await?;
+
+    let patterns = ignore_patterns(&data, category).await?;
+
+    let needed_bytes = payload
+        .target_free_bytes
+        .saturating_sub(usage.available_bytes);
+
+    let steps = cleanup::plan(
+        &scanned,
+        payload.policy,
+        &payload.ignored_elsewhere,
+        category.case_insensitive,
+        needed_bytes,
+        |path| crate::ignores::matches(&patterns, path, category.case_insensitive),
+    );
+    let planned_bytes = steps.iter().map(|step| step.reclaimable_bytes).sum();
+
+    Ok(Json(CleanupPlanResponse {
+        category_id: category.id.clone(),
+        available_bytes: usage.available_bytes,
+        target_free_bytes: payload.target_free_bytes,
+        needed_bytes,
+        planned_bytes,
+        satisfied: planned_bytes >= needed_bytes,
+        steps,
+    }))
+}
+
+// GET retention report
+// Returns what recent retention runs found and did, newest first
+pub async fn retention_report(State(data): State<config::Data>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(RetentionReportResponse {
+            runs: data.retention_log.runs(),
+        }),
+    )
+}
+
+// POST availability
+// Reports which other devices hold the current version of an item, according to Syncthing
+pub async fn post_availability(
+    State(data): State<config::Data>,
+    ApiJson(payload): ApiJson<AvailabilityRequest>,
+) -> Result<Json<AvailabilityResponse>, ApiError> {
+    validate_folder_path(&payload.folder_path)?;
+    let category = find_category(&data, &payload.category_id)?;
+
+    let client = data.syncthing_client().ok_or_else(|| {
+        ApiError::new(
+            ErrorCode::SyncthingNotConfigured,
+            "Syncthing isn't configured",
         )
-            .into_response(),
+    })?;
+
+    let category_base_path = build_category_base_path(&data.agent, category);
+    let target_path = match filesystem::resolve_path(
+        &category_base_path,
+        &payload.folder_path,
+        data.agent.symlinks,
+        category.case_insensitive,
+    ) {
+        Ok(Some(path)) if path.exists() => path,
+        Ok(_) => {
+            return Err(ApiError::new(
+                ErrorCode::ItemNotFound,
+                format!("Folder path '{:?}' not found", payload.folder_path),
+            ));
+        }
+        Err(error) => return Err(ApiError::scan(&category.name, error)),
+    };
+
+    // Syncthing knows items by their on-disk names, which may differ from the request in case or normalization
+    let relative: Vec<OsString> = target_path
+        .strip_prefix(&category_base_path)
+        .unwrap_or(&target_path)
+        .iter()
+        .map(OsStr::to_os_string)
+        .collect();
+
+    // A directory only counts as available when every file under it is
+    let symlinks = data.agent.symlinks;
+    let case_insensitive = category.case_insensitive;
+    let listed = tokio::task::spawn_blocking(move || {
+        let files =
+            hashing::collect_files(&category_base_path, &relative, symlinks, case_insensitive)?;
+        let files: Vec<String> = files
+            .unwrap_or_default()
+            .iter()
+            .map(|file| {
+                let relative: Vec<OsString> = file
+                    .path
+                    .strip_prefix(&category_base_path)
+                    .unwrap_or(&file.path)
+                    .iter()
+                    .map(OsStr::to_os_string)
+                    .collect();
+                lossy_folder_path(&relative).join("/")
+            })
+            .collect();
+        Ok(match files.is_empty() {
+            true => vec![lossy_folder_path(&relative).join("/")],
+            false => files,
+        })
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Listing item files failed: {}", err);
+        ApiError::new(ErrorCode::Internal, "Listing item files failed")
+    })?;
+    let files = listed.map_err(|error| ApiError::scan(&category.name, error))?;
+
+    // Syncthing may list this device too, which doesn't make the item safe to delete here
+    let my_id = client.my_id().await?;
+
+    // Every file is looked up, a few at a time so large directories neither crawl nor flood Syncthing
+    let folder_id = category.folder_id();
+    let mut files = files.into_iter();
+    let mut lookups = tokio::task::JoinSet::new();
+    let mut holders: Option<Vec<String>> = None;
+    loop {
+        while lookups.len() < MAX_AVAILABILITY_LOOKUPS {
+            let Some(file) = files.next() else {
+                break;
+            };
+            let client = client.clone();
+            let folder_id = folder_id.to_string();
+            lookups.spawn(async move {
+                let availability = client.availability(&folder_id, &file).await;
+                (file, availability)
+            });
+        }
+        let Some(lookup) = lookups.join_next().await else {
+            break;
+        };
+
+        let (file, availability) = lookup.map_err(|err| {
+            tracing::error!("Syncthing file lookup failed: {}", err);
+            ApiError::new(ErrorCode::Internal, "Syncthing file lookup failed")
+        })?;
+        let availability = availability.map_err(|err| {
+            let code = match err {
+                crate::syncthing::SyncthingError(crate::http::HttpError::Status(
+                    StatusCode::NOT_FOUND,
+                )) => ErrorCode::ItemNotFound,
+                _ => ErrorCode::SyncthingError,
+            };
+            ApiError::new(
+                code,
+                format!("Unable to look up '{}' in Syncthing: {}", file, err),
+            )
+        })?;
+
+        let ids: Vec<String> = availability
+            .into_iter()
+            .filter(|device| !device.from_temporary && device.id != my_id)
+            .map(|device| device.id)
+            .collect();
+        holders = Some(match holders {
+            Some(holders) => holders.into_iter().filter(|id| ids.contains(id)).collect(),
+            None => ids,
+        });
     }
+
+    let mut devices = vec![];
+    for device_id in holders.unwrap_or_default() {
+        let (folder_completion, folder_need_bytes, error) =
+            match client.completion(folder_id, &device_id).await {
+                Ok(completion) => (
+                    Some(completion.completion),
+                    Some(completion.need_bytes),
+                    None,
+                ),
+                Err(err) => (None, None, Some(err.to_string())),
+            };
+        devices.push(DeviceAvailability {
+            device_id,
+            folder_completion,
+            folder_need_bytes,
+            error,
+        });
+    }
+
+    Ok(Json(AvailabilityResponse {
+        category_id: category.id.clone(),
+        folder_path: payload.folder_path,
+        available: devices
+            .iter()
+            .filter(|device| device.error.is_none())
+            .count()
+            >= payload.min_devices,
+        min_devices: payload.min_devices,
+        devices,
+    }))
+}
+
+// POST diff
+// Compares a category with the same category on another agent
+pub async fn post_diff(
+    State(data): State<config::Data>,
+    ApiJson(payload): ApiJson<DiffRequest>,
+) -> Result<Json<DiffResponse>, ApiError> {
+    let category = find_category(&data, &payload.category_id)?;
+    let scanned = scan_category(&data, category).await?;
+
+    // The other agent's items, and its ignore statuses for every compared path when known
+    let client = payload
+        .remote
+        .as_ref()
+        .map(|remote| AgentClient::new(&remote.url, &remote.api_key));
+    let there = match (&client, payload.items) {
+        (Some(client), _) => client.category(&category.id).await?.items,
+        (None, Some(items)) => items,
+        (None, None) => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "Either items or remote must be given",
+            ));
+        }
+    };
+
+    let tree_diff = diff::compare(&scanned.items, &there, category.case_insensitive);
+    let paths = diff::union_paths(&scanned.items, &there, category.case_insensitive);
+
+    let ignored_there: Option<Vec<Vec<String>>> = match &client {
+        Some(client) => {
+            let request = BulkIgnoreStatusRequest {
+                items: paths
+                    .iter()
+                    .map(|folder_path| IgnoreStatusRequest {
+                        category_id: category.id.clone(),
+                        folder_path: folder_path.clone(),
+                        raw_components: vec![],
+                    })
+                    .collect(),
+            };
+            let statuses = client.ignore_status_bulk(&request).await?;
+            Some(
+                statuses
+                    .items
+                    .into_iter()
+                    .filter(|item| item.ignored)
+                    .map(|item| item.folder_path)
+                    .collect(),
+            )
+        }
+        None => payload.ignored_paths,
+    };
+
+    let ignore_mismatches = match &ignored_there {
+        Some(ignored_there) => {
+            let patterns = ignore_patterns(&data, category).await?;
+            let ignored_there: Vec<Vec<String>> = ignored_there
+                .iter()
+                .map(|ignored| diff::path_key(ignored, category.case_insensitive))
+                .collect();
+
+            // A path is also ignored when one of its parents is, on either side
+            diff::ignore_mismatches(
+                &paths,
+                |path| {
+                    (1..=path.len()).any(|len| {
+                        crate::ignores::matches(&patterns, &path[..len], category.case_insensitive)
+                    })
+                },
+                |path| {
+                    let path = diff::path_key(path, category.case_insensitive);
+                    ignored_there
+                        .iter()
+                        .any(|ignored| path.starts_with(ignored))
+                },
+            )
+        }
+        None => vec![],
+    };
+
+    Ok(Json(DiffResponse {
+        category_id: category.id.clone(),
+        only_here: tree_diff.only_here,
+        only_there: tree_diff.only_there,
+        size_mismatches: tree_diff.size_mismatches,
+        ignore_mismatches,
+        ignores_compared: ignored_there.is_some(),
+    }))
+}
+
+// GET duplicates
+// Finds files with identical content across every configured category
+pub async fn duplicates(
+    State(data): State<config::Data>,
+) -> Result<Json<DuplicatesResponse>, ApiError> {
+    let categories: Vec<(String, String, PathBuf, bool)> = data
+        .categories
+        .iter()
+        .map(|category| {
+            (
+                category.id.clone(),
+                category.name.clone(),
+                build_category_base_path(&data.agent, category),
+                category.case_insensitive,
+            )
+        })
+        .collect();
+
+    // Walking and hashing both touch the disk, so neither runs on the async workers
+    let cache = data.hash_cache.clone();
+    let symlinks = data.agent.symlinks;
+    let groups = tokio::task::spawn_blocking(move || {
+        let mut files: Vec<(String, hashing::FileEntry)> = Vec::new();
+        for (category_id, category_name, category_base_path, case_insensitive) in categories {
+            let listed =
+                hashing::collect_files(&category_base_path, &[], symlinks, case_insensitive)
+                    .map_err(|error| ApiError::scan(&category_name, error))?;
+            files.extend(
+                listed
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|file| (category_id.clone(), file)),
+            );
+        }
+        Ok::<_, ApiError>(hashing::find_duplicates(files, Some(&cache)))
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Duplicate detection failed: {}", err);
+        ApiError::new(ErrorCode::Internal, "Duplicate detection failed")
+    })??;
+
+    let reclaimable_bytes = groups.iter().map(|group| group.reclaimable_bytes).sum();
+
+    Ok(Json(DuplicatesResponse {
+        groups,
+        reclaimable_bytes,
+    }))
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::config::{AgentConfig, Category, Data};
     use axum::Router;
@@ -499,7 +1150,7 @@ mod tests {
         }
     }
 
-    fn create_test_data() -> (Data, TempDir) {
+    pub(crate) fn create_test_data() -> (Data, TempDir) {
         let temp_dir = TempDir::new().unwrap();
         let base_path = temp_dir.path().to_string_lossy().to_string();
 
@@ -512,29 +1163,56 @@ mod tests {
                 port: 3000,
                 base_path,
                 api_key: "550e8400-e29b-41d4-a716-446655440000".to_string(),
+                scan_threads: None,
+                symlinks: Default::default(),
+                retention_interval_minutes: None,
+                delete_guard: true,
             },
             categories: vec![
                 Category {
                     id: "movies".to_string(),
                     name: "Movies".to_string(),
                     relative_path: "movies".to_string(),
+                    case_insensitive: false,
+                    retention: None,
+                    syncthing_folder_id: None,
+                    ignore_backend: Default::default(),
+                    folder_type: Default::default(),
                 },
                 Category {
                     id: "tv".to_string(),
                     name: "TV Shows".to_string(),
                     relative_path: "tv".to_string(),
+                    case_insensitive: false,
+                    retention: None,
+                    syncthing_folder_id: None,
+                    ignore_backend: Default::default(),
+                    folder_type: Default::default(),
                 },
             ],
+            syncthing: None,
+            hash_cache: Default::default(),
+            retention_log: Default::default(),
+            folder_status_cache: Default::default(),
+            ignore_locks: Default::default(),
         };
 
         (data, temp_dir)
     }
 
-    fn create_test_router(data: Data) -> Router {
+    pub(crate) fn create_test_router(data: Data) -> Router {
         Router::new()
             .route("/", axum::routing::get(help))
             .route("/api/v1/categories", axum::routing::get(category_list))
             .route("/api/v1/categories/{id}", axum::routing::get(category_info))
+            .route(
+                "/api/v1/categories/{id}/local-changes",
+                axum::routing::get(category_local_changes),
+            )
+            .route(
+                "/api/v1/categories/{id}/revert",
+                axum::routing::post(post_category_revert),
+            )
             .route("/api/v1/items", axum::routing::post(post_item_info))
             .route("/api/v1/ignore", axum::routing::post(post_ignore))
             .route(
@@ -546,13 +1224,35 @@ mod tests {
                 axum::routing::post(post_ignore_status_bulk),
             )
             .route("/api/v1/delete", axum::routing::post(post_delete))
+            .route("/api/v1/hashes", axum::routing::post(post_hashes))
+            .route("/api/v1/duplicates", axum::routing::get(duplicates))
+            .route("/api/v1/disk-usage", axum::routing::get(disk_usage))
+            .route(
+                "/api/v1/cleanup-plan",
+                axum::routing::post(post_cleanup_plan),
+            )
+            .route("/api/v1/retention", axum::routing::get(retention_report))
+            .route(
+                "/api/v1/availability",
+                axum::routing::post(post_availability),
+            )
+            .route("/api/v1/diff", axum::routing::post(post_diff))
             .layer(axum::middleware::from_fn_with_state(
-                data.clone(),
+                data.agent.api_key.clone(),
                 crate::auth_middleware,
             ))
             .with_state(data)
     }
 
+    /// Serves a test agent over TCP, for tests talking to it as another agent would
+    pub(crate) async fn serve_test_agent(data: Data) -> String {
+        let app = create_test_router(data);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
     async fn setup_test_server() -> (TestServer, TempDir) {
         let (data, temp_dir) = create_test_data();
         let app = create_test_router(data);
@@ -560,6 +1260,16 @@ mod tests {
         (server, temp_dir)
     }
 
+    async fn setup_case_insensitive_test_server() -> (TestServer, TempDir) {
+        let (mut data, temp_dir) = create_test_data();
+        for category in data.categories.iter_mut() {
+            category.case_insensitive = true;
+        }
+        let app = create_test_router(data);
+        let server = TestServer::new(app).unwrap();
+        (server, temp_dir)
+    }
+
     // Helper endpoint tests
     #[tokio::test]
     async fn test_help_endpoint() {
@@ -677,17 +1387,61 @@ mod tests {
             .await;
         response.assert_status(StatusCode::NOT_FOUND);
 
-        let json: NotFoundResponse = response.json();
-        assert!(json.message.contains("Category ID nonexistent not found"));
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::CategoryNotFound);
+        assert!(json.message.contains("Category ID 'nonexistent' not found"));
     }
 
-    // Item endpoint tests (POST)
     #[tokio::test]
-    async fn test_post_item_info_success() {
-        let (server, _temp_dir) = setup_test_server().await;
+    async fn test_category_info_unreadable() {
+        let (server, temp_dir) = setup_test_server().await;
+        fs::remove_dir_all(temp_dir.path().join("tv")).unwrap();
 
-        let request_body = ItemInfoRequest {
-            item_path: vec![MOVIES_ID.to_string()],
+        let response = server
+            .get("/api/v1/categories/tv")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::IoError);
+        assert!(json.message.contains("TV Shows"));
+        let error = json.error.unwrap();
+        assert_eq!(error.kind, "NotFound");
+        // Server paths are never exposed
+        assert_eq!(error.path, "/");
+    }
+
+    #[tokio::test]
+    async fn test_category_list_reports_unreadable_category() {
+        let (server, temp_dir) = setup_test_server().await;
+        fs::remove_dir_all(temp_dir.path().join("tv")).unwrap();
+
+        let response = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: CategoryListingResponse = response.json();
+        let tv_category = json.items.iter().find(|item| item.id == "tv").unwrap();
+        assert!(tv_category.items.is_empty());
+        assert_eq!(tv_category.errors.len(), 1);
+        assert_eq!(tv_category.errors[0].kind, "NotFound");
+        assert_eq!(tv_category.errors[0].path, "/");
+
+        let movies_category = json.items.iter().find(|item| item.id == "movies").unwrap();
+        assert!(movies_category.errors.is_empty());
+    }
+
+    // Item endpoint tests (POST)
+    #[tokio::test]
+    async fn test_post_item_info_success() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let request_body = ItemInfoRequest {
+            item_path: vec![MOVIES_ID.to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -709,6 +1463,7 @@ mod tests {
 
         let request_body = ItemInfoRequest {
             item_path: vec![NONEXISTENT_ID.to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -718,7 +1473,8 @@ mod tests {
             .await;
         response.assert_status(StatusCode::NOT_FOUND);
 
-        let json: NotFoundResponse = response.json();
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::CategoryNotFound);
         assert!(json.message.contains("Category ID"));
     }
 
@@ -728,6 +1484,7 @@ mod tests {
 
         let request_body = ItemInfoRequest {
             item_path: vec!["invalid_category".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -737,7 +1494,7 @@ mod tests {
             .await;
         response.assert_status(StatusCode::NOT_FOUND);
 
-        let json: NotFoundResponse = response.json();
+        let json: ApiError = response.json();
         assert!(
             json.message
                 .contains("Category ID 'invalid_category' not found")
@@ -754,6 +1511,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -786,6 +1544,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -808,6 +1567,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec![],
+            raw_components: vec![],
         };
 
         let response = server
@@ -817,11 +1577,89 @@ mod tests {
             .await;
         response.assert_status(StatusCode::BAD_REQUEST);
 
-        let json: IgnoreResponse = response.json();
-        assert!(!json.success);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::EmptyPath);
         assert!(json.message.contains("Folder path cannot be empty"));
     }
 
+    #[tokio::test]
+    async fn test_paths_outside_category_rejected() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["..".to_string(), "tv".to_string()],
+                raw_components: vec![],
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::PathOutsideCategory);
+
+        let stignore = fs::read_to_string(temp_dir.path().join("movies/.stignore")).unwrap();
+        assert!(stignore.is_empty());
+
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&DeleteRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["Movie 1 (2023)/..".to_string()],
+                raw_components: vec![],
+                force: true,
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::PathOutsideCategory);
+        assert!(temp_dir.path().join("movies/Movie 1 (2023)").exists());
+
+        // A line break would add a pattern line of its own
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["Movie 1 (2023)\n*".to_string()],
+                raw_components: vec![],
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::InvalidRequest);
+        let stignore = fs::read_to_string(temp_dir.path().join("movies/.stignore")).unwrap();
+        assert!(stignore.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_post_delete_refuses_symlinked_and_syncthing_paths() {
+        let (server, temp_dir) = setup_test_server().await;
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("keep.txt"), "keep").unwrap();
+        std::os::unix::fs::symlink(&outside, temp_dir.path().join("movies/Link")).unwrap();
+
+        for folder_path in [vec!["Link", "keep.txt"], vec![".stversions"]] {
+            let response = server
+                .post("/api/v1/delete")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .json(&DeleteRequest {
+                    category_id: MOVIES_ID.to_string(),
+                    folder_path: folder_path.iter().map(|name| name.to_string()).collect(),
+                    raw_components: vec![],
+                    force: true,
+                })
+                .await;
+            response.assert_status(StatusCode::NOT_FOUND);
+        }
+        assert!(outside.join("keep.txt").exists());
+        assert!(temp_dir.path().join("movies/.stversions").exists());
+    }
+
     #[tokio::test]
     async fn test_post_ignore_invalid_category() {
         let (server, _temp_dir) = setup_test_server().await;
@@ -829,6 +1667,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: NONEXISTENT_ID.to_string(),
             folder_path: vec!["Some Movie".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -836,10 +1675,10 @@ mod tests {
             .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
             .json(&request_body)
             .await;
-        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_status(StatusCode::NOT_FOUND);
 
-        let json: IgnoreResponse = response.json();
-        assert!(!json.success);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::CategoryNotFound);
         assert!(
             json.message
                 .contains("Category ID 'nonexistent_id' not found")
@@ -854,6 +1693,7 @@ mod tests {
         let request_body = IgnoreRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Non-existent Movie (2025)".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -884,6 +1724,7 @@ mod tests {
         let request_body = IgnoreStatusRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -908,6 +1749,7 @@ mod tests {
         let request_body = IgnoreStatusRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -928,6 +1770,7 @@ mod tests {
         let request_body = IgnoreStatusRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec![],
+            raw_components: vec![],
         };
 
         let response = server
@@ -937,8 +1780,8 @@ mod tests {
             .await;
         response.assert_status(StatusCode::BAD_REQUEST);
 
-        let json: IgnoreStatusResponse = response.json();
-        assert!(!json.ignored);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::EmptyPath);
     }
 
     #[tokio::test]
@@ -948,6 +1791,7 @@ mod tests {
         let request_body = IgnoreStatusRequest {
             category_id: NONEXISTENT_ID.to_string(),
             folder_path: vec!["Some Movie".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -955,10 +1799,10 @@ mod tests {
             .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
             .json(&request_body)
             .await;
-        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_status(StatusCode::NOT_FOUND);
 
-        let json: IgnoreStatusResponse = response.json();
-        assert!(!json.ignored);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::CategoryNotFound);
     }
 
     #[tokio::test]
@@ -972,6 +1816,7 @@ mod tests {
         let request_body = IgnoreStatusRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Non-existent Movie (2025)".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -993,6 +1838,7 @@ mod tests {
         let request_body = IgnoreStatusRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Another Non-existent Movie (2026)".to_string()],
+            raw_components: vec![],
         };
 
         let response = server
@@ -1020,14 +1866,22 @@ mod tests {
                 IgnoreStatusRequest {
                     category_id: MOVIES_ID.to_string(),
                     folder_path: vec!["Movie 1 (2023)".to_string()], // ignored
+                    raw_components: vec![],
                 },
                 IgnoreStatusRequest {
                     category_id: MOVIES_ID.to_string(),
                     folder_path: vec!["Movie 2 (2023)".to_string()], // not ignored
+                    raw_components: vec![],
                 },
                 IgnoreStatusRequest {
                     category_id: "invalid_category".to_string(),
                     folder_path: vec!["Any Movie".to_string()], // invalid category
+                    raw_components: vec![],
+                },
+                IgnoreStatusRequest {
+                    category_id: MOVIES_ID.to_string(),
+                    folder_path: vec![], // empty path
+                    raw_components: vec![],
                 },
             ],
         };
@@ -1041,23 +1895,75 @@ mod tests {
 
         let json: BulkIgnoreStatusResponse = response.json();
 
-        // Should return 3 items
-        assert_eq!(json.items.len(), 3);
+        // Should return 4 items
+        assert_eq!(json.items.len(), 4);
 
         // First item should be ignored
         assert_eq!(json.items[0].category_id, MOVIES_ID);
         assert_eq!(json.items[0].folder_path, vec!["Movie 1 (2023)"]);
         assert!(json.items[0].ignored);
+        assert!(json.items[0].error.is_none());
 
         // Second item should not be ignored
         assert_eq!(json.items[1].category_id, MOVIES_ID);
         assert_eq!(json.items[1].folder_path, vec!["Movie 2 (2023)"]);
         assert!(!json.items[1].ignored);
+        assert!(json.items[1].error.is_none());
 
-        // Third item (invalid category) should not be ignored
+        // Misconfigured items report why they couldn't be checked
         assert_eq!(json.items[2].category_id, "invalid_category");
         assert_eq!(json.items[2].folder_path, vec!["Any Movie"]);
         assert!(!json.items[2].ignored);
+        assert_eq!(
+            json.items[2].error.as_ref().unwrap().code,
+            ErrorCode::CategoryNotFound
+        );
+
+        assert!(!json.items[3].ignored);
+        assert_eq!(
+            json.items[3].error.as_ref().unwrap().code,
+            ErrorCode::EmptyPath
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_ignore_status_bulk_reports_matched_rule() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        // Comment lines never match, and the rule is reported as written
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        std::fs::write(&stignore_path, "// comment\n(?i)/movie 1 (2023)\n").unwrap();
+
+        let request_body = BulkIgnoreStatusRequest {
+            items: vec![
+                IgnoreStatusRequest {
+                    category_id: MOVIES_ID.to_string(),
+                    folder_path: vec!["Movie 1 (2023)".to_string()],
+                    raw_components: vec![],
+                },
+                IgnoreStatusRequest {
+                    category_id: MOVIES_ID.to_string(),
+                    folder_path: vec!["Movie 2 (2023)".to_string()],
+                    raw_components: vec![],
+                },
+            ],
+        };
+
+        let response = server
+            .post("/api/v1/ignore-status-bulk")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: BulkIgnoreStatusResponse = response.json();
+        assert!(json.items[0].ignored);
+        assert_eq!(
+            json.items[0].matched_rule.as_deref(),
+            Some("(?i)/movie 1 (2023)")
+        );
+        assert!(!json.items[1].ignored);
+        assert!(json.items[1].matched_rule.is_none());
     }
 
     // Delete endpoint tests
@@ -1068,6 +1974,8 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+            force: true,
         };
 
         let response = server
@@ -1084,6 +1992,78 @@ mod tests {
         assert!(json.message.contains("Successfully deleted"));
     }
 
+    #[tokio::test]
+    async fn test_post_delete_refuses_unignored_path() {
+        let (server, temp_dir) = setup_test_server().await;
+        let movie = temp_dir.path().join("movies").join("Movie 1 (2023)");
+
+        let request_body = DeleteRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+            force: false,
+        };
+
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::CONFLICT);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::NotIgnored);
+        assert!(movie.exists());
+
+        // Once ignored, Syncthing won't send the delete to other devices
+        let ignore_request = IgnoreRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+        };
+        server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&ignore_request)
+            .await
+            .assert_status(StatusCode::OK);
+
+        server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .assert_status(StatusCode::OK);
+        assert!(!movie.exists());
+    }
+
+    #[tokio::test]
+    async fn test_post_delete_receive_only_category() {
+        let (mut data, temp_dir) = create_test_data();
+        data.categories[0].folder_type = config::FolderType::ReceiveOnly;
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let request_body = DeleteRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+            force: false,
+        };
+
+        server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await
+            .assert_status(StatusCode::OK);
+        assert!(
+            !temp_dir
+                .path()
+                .join("movies")
+                .join("Movie 1 (2023)")
+                .exists()
+        );
+    }
+
     #[tokio::test]
     async fn test_post_delete_not_found() {
         let (server, _temp_dir) = setup_test_server().await;
@@ -1091,6 +2071,8 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["Non-existent Movie (2025)".to_string()],
+            raw_components: vec![],
+            force: false,
         };
 
         let response = server
@@ -1100,10 +2082,39 @@ mod tests {
             .await;
         response.assert_status(StatusCode::NOT_FOUND);
 
-        let json: DeleteResponse = response.json();
-        assert!(!json.success);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::ItemNotFound);
         assert!(json.message.contains("not found"));
-        assert!(json.deleted_path.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_bodies() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        // Malformed JSON, a missing field and a missing body are all invalid requests
+        let responses = [
+            server
+                .post("/api/v1/delete")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .content_type("application/json")
+                .text("{\"category_id\": ")
+                .await,
+            server
+                .post("/api/v1/ignore")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .json(&serde_json::json!({ "category_id": MOVIES_ID }))
+                .await,
+            server
+                .post("/api/v1/items")
+                .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+                .await,
+        ];
+        for response in responses {
+            response.assert_status(StatusCode::BAD_REQUEST);
+            let json: ApiError = response.json();
+            assert_eq!(json.code, ErrorCode::InvalidRequest);
+            assert!(!json.message.is_empty());
+        }
     }
 
     #[tokio::test]
@@ -1113,6 +2124,8 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec![],
+            raw_components: vec![],
+            force: false,
         };
 
         let response = server
@@ -1122,10 +2135,9 @@ mod tests {
             .await;
         response.assert_status(StatusCode::BAD_REQUEST);
 
-        let json: DeleteResponse = response.json();
-        assert!(!json.success);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::EmptyPath);
         assert!(json.message.contains("Folder path cannot be empty"));
-        assert!(json.deleted_path.is_none());
     }
 
     #[tokio::test]
@@ -1135,6 +2147,8 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: NONEXISTENT_ID.to_string(),
             folder_path: vec!["Some Movie".to_string()],
+            raw_components: vec![],
+            force: false,
         };
 
         let response = server
@@ -1142,15 +2156,14 @@ mod tests {
             .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
             .json(&request_body)
             .await;
-        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_status(StatusCode::NOT_FOUND);
 
-        let json: DeleteResponse = response.json();
-        assert!(!json.success);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::CategoryNotFound);
         assert!(
             json.message
                 .contains("Category ID 'nonexistent_id' not found")
         );
-        assert!(json.deleted_path.is_none());
     }
 
     #[tokio::test]
@@ -1164,6 +2177,8 @@ mod tests {
         let request_body = DeleteRequest {
             category_id: MOVIES_ID.to_string(),
             folder_path: vec!["test-file.txt".to_string()],
+            raw_components: vec![],
+            force: true,
         };
 
         let response = server
@@ -1183,6 +2198,1057 @@ mod tests {
         assert!(!test_file_path.exists());
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_utf8_names_round_trip() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let (server, temp_dir) = setup_test_server().await;
+
+        // A UTF-8 name that looks percent-encoded, holding a name that isn't UTF-8
+        let dir_name = "Movie %41 (2023)";
+        let file_name = std::ffi::OsStr::from_bytes(b"Extras \xff.mkv");
+        let dir_path = temp_dir.path().join("movies").join(dir_name);
+        fs::create_dir(&dir_path).unwrap();
+        let file_path = dir_path.join(file_name);
+        fs::write(&file_path, "extras").unwrap();
+
+        // The listing exposes a raw name for the lossy filename
+        let response = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&ItemInfoRequest {
+                item_path: vec![MOVIES_ID.to_string(), dir_name.to_string()],
+                raw_components: vec![],
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: ItemInfoResponse = response.json();
+        let extras = json
+            .item
+            .items
+            .iter()
+            .find(|item| item.raw_name.is_some())
+            .unwrap();
+        let raw_name = extras.raw_name.clone().unwrap();
+        assert_eq!(raw_name, "Extras %FF.mkv");
+
+        // The raw name can be used to address the file, without decoding the plain name
+        let response = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&ItemInfoRequest {
+                item_path: vec![
+                    MOVIES_ID.to_string(),
+                    dir_name.to_string(),
+                    raw_name.clone(),
+                ],
+                raw_components: vec![2],
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let folder_path = vec![dir_name.to_string(), raw_name];
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: folder_path.clone(),
+                raw_components: vec![1],
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: IgnoreResponse = response.json();
+        assert_eq!(
+            json.ignored_path.unwrap(),
+            "/Movie %41 (2023)/Extras \u{fffd}.mkv"
+        );
+
+        let response = server
+            .post("/api/v1/ignore-status")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreStatusRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: folder_path.clone(),
+                raw_components: vec![1],
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: IgnoreStatusResponse = response.json();
+        assert!(json.ignored);
+
+        // Once ignored, the delete guard lets the file go
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&DeleteRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path,
+                raw_components: vec![1],
+                force: false,
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        assert!(!file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_raw_components_out_of_range() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let response = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&ItemInfoRequest {
+                item_path: vec![MOVIES_ID.to_string(), "Movie 1 (2023)".to_string()],
+                raw_components: vec![0],
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::InvalidRawName);
+
+        let response = server
+            .post("/api/v1/ignore-status")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreStatusRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["Movie 1 (2023)".to_string()],
+                raw_components: vec![1],
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::InvalidRawName);
+    }
+
+    // Hash endpoint tests
+    #[tokio::test]
+    async fn test_post_hashes_streams_file_hashes() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let request_body = HashRequest {
+            category_id: "tv".to_string(),
+            folder_path: vec!["Show 1 (2021)".to_string()],
+            raw_components: vec![],
+            skip_cache: false,
+        };
+
+        let response = server
+            .post("/api/v1/hashes")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let lines: Vec<FileHashResponse> = response
+            .text()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 5);
+
+        // SHA-256 of "test episode content"
+        let expected = "966512e8b8c8db8f1c49a76356ed6bfbf4265592af92429fe896cc4839afd083";
+        let episode = lines
+            .iter()
+            .find(|line| line.path.ends_with(&["S01E01 - Ep 1.mkv".to_string()]))
+            .unwrap();
+        assert_eq!(episode.path[0], "Show 1 (2021)");
+        assert_eq!(episode.size, 20);
+        assert_eq!(episode.sha256.as_deref(), Some(expected));
+        assert!(episode.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_post_hashes_not_found() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        let request_body = HashRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Non-existent Movie (2025)".to_string()],
+            raw_components: vec![],
+            skip_cache: false,
+        };
+
+        let response = server
+            .post("/api/v1/hashes")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    // Disk usage tests
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_disk_usage() {
+        let (server, temp_dir) = setup_test_server().await;
+        fs::remove_dir_all(temp_dir.path().join("tv")).unwrap();
+
+        let response = server
+            .get("/api/v1/disk-usage")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: DiskUsageResponse = response.json();
+        assert_eq!(json.categories.len(), 2);
+
+        let movies = &json.categories[0];
+        assert_eq!(movies.category_id, "movies");
+        let usage = movies.usage.as_ref().unwrap();
+        assert!(usage.total_bytes > 0);
+        assert!(movies.error.is_none());
+
+        // A missing category reports its error without failing the others
+        let tv = &json.categories[1];
+        assert_eq!(tv.category_id, "tv");
+        assert!(tv.usage.is_none());
+        assert_eq!(tv.error.as_ref().unwrap().kind, "NotFound");
+    }
+
+    // Cleanup planning tests
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_post_cleanup_plan() {
+        let (server, temp_dir) = setup_test_server().await;
+        let movies = temp_dir.path().join("movies");
+        fs::write(
+            movies.join("Movie 1 (2023)").join("extras.mkv"),
+            vec![0u8; 4096],
+        )
+        .unwrap();
+        fs::write(
+            movies.join("Movie 2 (2024)").join("extras.mkv"),
+            vec![0u8; 8192],
+        )
+        .unwrap();
+        fs::write(movies.join(".stignore"), "/Movie 1 (2023)\n").unwrap();
+
+        let request = CleanupPlanRequest {
+            category_id: "movies".to_string(),
+            target_free_bytes: u64::MAX,
+            policy: cleanup::CleanupPolicy::Largest,
+            ignored_elsewhere: vec![],
+        };
+
+        let response = server
+            .post("/api/v1/cleanup-plan")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: CleanupPlanResponse = response.json();
+        assert_eq!(json.category_id, "movies");
+        assert!(!json.satisfied);
+        assert_eq!(json.planned_bytes, 12 * 1024);
+        assert_eq!(json.steps.len(), 2);
+        assert_eq!(json.steps[0].folder_path, vec!["Movie 2 (2024)"]);
+        assert!(!json.steps[0].ignored);
+        assert_eq!(json.steps[1].folder_path, vec!["Movie 1 (2023)"]);
+        assert!(json.steps[1].ignored);
+
+        // Nothing is needed once the target is already met
+        let request = CleanupPlanRequest {
+            target_free_bytes: 0,
+            ..request
+        };
+        let response = server
+            .post("/api/v1/cleanup-plan")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        let json: CleanupPlanResponse = response.json();
+        assert!(json.satisfied);
+        assert!(json.steps.is_empty());
+
+        let request = CleanupPlanRequest {
+            category_id: "music".to_string(),
+            ..request
+        };
+        server
+            .post("/api/v1/cleanup-plan")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    // Syncthing integration tests
+    #[tokio::test]
+    async fn test_ignore_and_delete_request_syncthing_rescan() {
+        let mut mock = crate::syncthing::tests::mock_syncthing().await;
+        let (mut data, _temp_dir) = create_test_data();
+        data.syncthing = Some(mock.config.clone());
+        data.categories[0].syncthing_folder_id = Some("abcde-12345".to_string());
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let request = IgnoreRequest {
+            category_id: "movies".to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+        };
+        server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await
+            .assert_status(StatusCode::OK);
+        assert_eq!(mock.scans.recv().await.unwrap(), "abcde-12345");
+
+        let request = DeleteRequest {
+            category_id: "tv".to_string(),
+            folder_path: vec!["Show 2 (2022)".to_string()],
+            raw_components: vec![],
+            force: true,
+        };
+        server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await
+            .assert_status(StatusCode::OK);
+        assert_eq!(mock.scans.recv().await.unwrap(), "tv");
+    }
+
+    #[tokio::test]
+    async fn test_syncthing_ignore_backend() {
+        let mock = crate::syncthing::tests::mock_syncthing().await;
+        let (mut data, temp_dir) = create_test_data();
+        data.syncthing = Some(mock.config.clone());
+        data.categories[0].ignore_backend = config::IgnoreBackend::Syncthing;
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let request = IgnoreRequest {
+            category_id: "movies".to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+        };
+        server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await
+            .assert_status(StatusCode::OK);
+
+        // The rule went to Syncthing rather than straight into .stignore
+        assert_eq!(
+            mock.ignores.lock().unwrap()["movies"],
+            vec!["/Movie 1 (2023)"]
+        );
+        let stignore = fs::read_to_string(temp_dir.path().join("movies").join(".stignore"))
+            .unwrap_or_default();
+        assert!(!stignore.contains("Movie 1 (2023)"));
+
+        let request = IgnoreStatusRequest {
+            category_id: "movies".to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+        };
+        let response = server
+            .post("/api/v1/ignore-status")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: IgnoreStatusResponse = response.json();
+        assert!(json.ignored);
+    }
+
+    #[tokio::test]
+    async fn test_syncthing_ignore_backend_not_configured() {
+        let (mut data, _temp_dir) = create_test_data();
+        data.categories[0].ignore_backend = config::IgnoreBackend::Syncthing;
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let request = IgnoreRequest {
+            category_id: "movies".to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+        };
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::SyncthingNotConfigured);
+    }
+
+    #[tokio::test]
+    async fn test_syncthing_ignore_backend_unreachable() {
+        // Nothing listens on the discard port
+        let (mut data, _temp_dir) = create_test_data();
+        data.categories[0].ignore_backend = config::IgnoreBackend::Syncthing;
+        data.syncthing = Some(config::SyncthingConfig {
+            url: "http://127.0.0.1:9".to_string(),
+            api_key: "abc123".to_string(),
+            discover: None,
+            status_ttl_secs: None,
+        });
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let request = IgnoreRequest {
+            category_id: "movies".to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string()],
+            raw_components: vec![],
+        };
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        response.assert_status(StatusCode::BAD_GATEWAY);
+        let json: ApiError = response.json();
+        assert_eq!(json.code, ErrorCode::SyncthingError);
+    }
+
+    #[tokio::test]
+    async fn test_category_list_reports_sync_state() {
+        let mock = crate::syncthing::tests::mock_syncthing().await;
+        let (mut data, _temp_dir) = create_test_data();
+        data.syncthing = Some(mock.config.clone());
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let response = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: CategoryListingResponse = response.json();
+        assert_eq!(json.sync.len(), 2);
+        assert_eq!(json.sync[0].category_id, "movies");
+        let status = json.sync[0].status.as_ref().unwrap();
+        assert_eq!(status.state, "syncing");
+        assert_eq!(status.need_bytes, 1024);
+    }
+
+    #[tokio::test]
+    async fn test_category_list_with_unreachable_syncthing() {
+        // Nothing listens on the discard port
+        let (mut data, _temp_dir) = create_test_data();
+        data.syncthing = Some(config::SyncthingConfig {
+            url: "http://127.0.0.1:9".to_string(),
+            api_key: "abc123".to_string(),
+            discover: None,
+            status_ttl_secs: None,
+        });
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let response = server
+            .get("/api/v1/categories")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: CategoryListingResponse = response.json();
+        assert_eq!(json.items.len(), 2);
+        assert_eq!(json.sync.len(), 2);
+        assert!(json.sync[0].status.is_none());
+        assert!(json.sync[0].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_post_availability() {
+        use crate::syncthing::tests::{FAILING_DEVICE, LOCAL_DEVICE, PARTIAL_DEVICE};
+
+        let mock = crate::syncthing::tests::mock_syncthing().await;
+        mock.availability.lock().unwrap().insert(
+            "Movie 1 (2023)/Movie 1 (2023).mkv".to_string(),
+            vec![
+                LOCAL_DEVICE.to_string(),
+                "REMOTE-DEVICE".to_string(),
+                PARTIAL_DEVICE.to_string(),
+                FAILING_DEVICE.to_string(),
+            ],
+        );
+        let (mut data, temp_dir) = create_test_data();
+        data.syncthing = Some(mock.config.clone());
+        for category in data.categories.iter_mut() {
+            category.case_insensitive = true;
+        }
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let request = AvailabilityRequest {
+            category_id: "movies".to_string(),
+            folder_path: vec![
+                "Movie 1 (2023)".to_string(),
+                "Movie 1 (2023).mkv".to_string(),
+            ],
+            min_devices: 3,
+        };
+        let response = server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        // The local device never counts towards availability, nor does one whose completion failed
+        let json: AvailabilityResponse = response.json();
+        assert_eq!(json.devices.len(), 3);
+        assert_eq!(json.devices[0].device_id, "REMOTE-DEVICE");
+        assert_eq!(json.devices[0].folder_completion, Some(100.0));
+        assert_eq!(json.devices[1].folder_need_bytes, Some(2048));
+        assert!(json.devices[2].error.is_some());
+        assert!(!json.available);
+
+        let request = AvailabilityRequest {
+            min_devices: 2,
+            ..request
+        };
+        let response = server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        let json: AvailabilityResponse = response.json();
+        assert!(json.available);
+
+        // Names are looked up in Syncthing as they are on disk
+        let response = server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&AvailabilityRequest {
+                folder_path: vec!["movie 1 (2023)".to_string()],
+                ..request.clone()
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: AvailabilityResponse = response.json();
+        assert!(json.available);
+
+        // A directory needs every file under it held by the same devices
+        fs::write(
+            temp_dir
+                .path()
+                .join("movies/Movie 1 (2023)/Movie 1 (2023).srt"),
+            "subtitles",
+        )
+        .unwrap();
+        mock.availability.lock().unwrap().insert(
+            "Movie 1 (2023)/Movie 1 (2023).srt".to_string(),
+            vec!["REMOTE-DEVICE".to_string()],
+        );
+        let response = server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&AvailabilityRequest {
+                folder_path: vec!["Movie 1 (2023)".to_string()],
+                ..request.clone()
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: AvailabilityResponse = response.json();
+        assert_eq!(json.devices.len(), 1);
+        assert_eq!(json.devices[0].device_id, "REMOTE-DEVICE");
+        assert!(!json.available);
+
+        let request = AvailabilityRequest {
+            folder_path: vec!["Movie 3 (2025)".to_string()],
+            ..request
+        };
+        server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_post_availability_looks_up_every_file() {
+        let mock = crate::syncthing::tests::mock_syncthing().await;
+        let (mut data, temp_dir) = create_test_data();
+        data.syncthing = Some(mock.config.clone());
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        // More files than are looked up at once, all held elsewhere
+        let extras = temp_dir.path().join("movies/Movie 1 (2023)/Extras");
+        fs::create_dir(&extras).unwrap();
+        for index in 0..MAX_AVAILABILITY_LOOKUPS * 3 {
+            let name = format!("Extra {}.mkv", index);
+            fs::write(extras.join(&name), "extra").unwrap();
+            mock.availability.lock().unwrap().insert(
+                format!("Movie 1 (2023)/Extras/{}", name),
+                vec!["REMOTE-DEVICE".to_string()],
+            );
+        }
+
+        let request = AvailabilityRequest {
+            category_id: "movies".to_string(),
+            folder_path: vec!["Movie 1 (2023)".to_string(), "Extras".to_string()],
+            min_devices: 1,
+        };
+        let response = server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: AvailabilityResponse = response.json();
+        assert_eq!(json.devices.len(), 1);
+        assert!(json.available);
+
+        // A file Syncthing doesn't know fails the whole lookup
+        fs::write(extras.join("Unknown.mkv"), "extra").unwrap();
+        let response = server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request)
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+        let json: serde_json::Value = response.json();
+        assert_eq!(json["code"], "item_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_post_availability_needs_local_device_id() {
+        let (mut data, _temp_dir) = create_test_data();
+        // Nothing listens here, so the local device ID can't be looked up
+        data.syncthing = Some(config::SyncthingConfig {
+            url: "http://127.0.0.1:9".to_string(),
+            api_key: "syncthing-test-key".to_string(),
+            discover: None,
+            status_ttl_secs: None,
+        });
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let response = server
+            .post("/api/v1/availability")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&AvailabilityRequest {
+                category_id: "movies".to_string(),
+                folder_path: vec!["Movie 1 (2023)".to_string()],
+                min_devices: 1,
+            })
+            .await;
+        response.assert_status(StatusCode::BAD_GATEWAY);
+        let json: serde_json::Value = response.json();
+        assert_eq!(json["code"], "syncthing_error");
+    }
+
+    #[tokio::test]
+    async fn test_diff_with_remote_agent() {
+        let (here, here_dir) = create_test_data();
+        let movie1_file = here_dir
+            .path()
+            .join("movies/Movie 1 (2023)/Movie 1 (2023).mkv");
+        fs::write(&movie1_file, vec![0u8; 12 * 1024]).unwrap();
+        fs::write(
+            here_dir.path().join("movies/.stignore"),
+            "/Movie 2 (2024)\n",
+        )
+        .unwrap();
+        fs::remove_dir_all(here_dir.path().join("tv/Show 2 (2022)")).unwrap();
+        let server = TestServer::new(create_test_router(here)).unwrap();
+
+        let (there, there_dir) = create_test_data();
+        let api_key = there.agent.api_key.clone();
+        fs::remove_dir_all(there_dir.path().join("tv/Show 1 (2021)/Season 2")).unwrap();
+        let url = serve_test_agent(there).await;
+        let remote = serde_json::json!({ "url": url, "api_key": api_key });
+
+        let response = server
+            .post("/api/v1/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({ "category_id": "movies", "remote": remote }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: DiffResponse = response.json();
+        assert!(json.only_here.is_empty());
+        assert!(json.only_there.is_empty());
+        assert_eq!(json.size_mismatches.len(), 1);
+        assert_eq!(
+            json.size_mismatches[0].folder_path,
+            vec!["Movie 1 (2023)", "Movie 1 (2023).mkv"]
+        );
+        assert!(json.ignores_compared);
+        assert_eq!(json.ignore_mismatches.len(), 1);
+        assert_eq!(
+            json.ignore_mismatches[0].folder_path,
+            vec!["Movie 2 (2024)"]
+        );
+        assert!(json.ignore_mismatches[0].ignored_here);
+
+        let response = server
+            .post("/api/v1/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({ "category_id": "tv", "remote": remote }))
+            .await;
+        let json: DiffResponse = response.json();
+        assert_eq!(json.only_here.len(), 1);
+        assert_eq!(
+            json.only_here[0].folder_path,
+            vec!["Show 1 (2021)", "Season 2"]
+        );
+        assert_eq!(json.only_there.len(), 1);
+        assert_eq!(json.only_there[0].folder_path, vec!["Show 2 (2022)"]);
+        assert!(json.ignore_mismatches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_diff_with_directory_ignored_on_both_sides() {
+        let (here, here_dir) = create_test_data();
+        fs::write(
+            here_dir.path().join("movies/.stignore"),
+            "/Movie 1 (2023)\n",
+        )
+        .unwrap();
+        let server = TestServer::new(create_test_router(here)).unwrap();
+
+        let (there, there_dir) = create_test_data();
+        let api_key = there.agent.api_key.clone();
+        fs::write(
+            there_dir.path().join("movies/.stignore"),
+            "/Movie 1 (2023)\n",
+        )
+        .unwrap();
+        let url = serve_test_agent(there).await;
+
+        // The directory's contents are ignored through it on both agents
+        let response = server
+            .post("/api/v1/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({
+                "category_id": "movies",
+                "remote": { "url": url, "api_key": api_key },
+            }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: DiffResponse = response.json();
+        assert!(json.ignores_compared);
+        assert!(json.ignore_mismatches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_diff_with_given_tree() {
+        let (server, _temp_dir) = setup_test_server().await;
+
+        // A tree without ignore statuses only compares items
+        let response = server
+            .post("/api/v1/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({ "category_id": "movies", "items": [] }))
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: DiffResponse = response.json();
+        assert_eq!(json.only_here.len(), 2);
+        assert!(json.only_there.is_empty());
+        assert!(!json.ignores_compared);
+
+        let response = server
+            .post("/api/v1/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({
+                "category_id": "movies",
+                "items": [],
+                "ignored_paths": [["Movie 1 (2023)"]],
+            }))
+            .await;
+        let json: DiffResponse = response.json();
+        assert!(json.ignores_compared);
+        assert_eq!(json.ignore_mismatches.len(), 1);
+        assert!(json.ignore_mismatches[0].ignored_there);
+
+        server
+            .post("/api/v1/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({ "category_id": "movies" }))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        server
+            .post("/api/v1/diff")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&serde_json::json!({ "category_id": NONEXISTENT_ID, "items": [] }))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_receive_only_local_changes_and_revert() {
+        let mock = crate::syncthing::tests::mock_syncthing().await;
+        mock.local_changes.lock().unwrap().insert(
+            "movies".to_string(),
+            vec![crate::syncthing::LocalChange {
+                name: "Movie 1 (2023)/Movie 1 (2023).mkv".to_string(),
+                size: 20,
+                deleted: true,
+                modified: None,
+            }],
+        );
+        let (mut data, _temp_dir) = create_test_data();
+        data.syncthing = Some(mock.config.clone());
+        data.categories[0].folder_type = config::FolderType::ReceiveOnly;
+        let server = TestServer::new(create_test_router(data)).unwrap();
+
+        let response = server
+            .get("/api/v1/categories/movies/local-changes")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: LocalChangesResponse = response.json();
+        assert_eq!(json.items.len(), 1);
+        assert!(json.items[0].deleted);
+
+        let response = server
+            .post("/api/v1/categories/movies/revert")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: RevertResponse = response.json();
+        assert!(json.message.contains("Reverted"));
+        assert!(mock.local_changes.lock().unwrap().is_empty());
+
+        // Send-receive folders have no local changes to revert
+        server
+            .post("/api/v1/categories/tv/revert")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await
+            .assert_status(StatusCode::CONFLICT);
+    }
+
+    // Retention tests
+    #[tokio::test]
+    async fn test_retention_report_and_enforce() {
+        let (mut data, temp_dir) = create_test_data();
+        let tv = temp_dir.path().join("tv");
+        data.categories[1].retention = Some(config::RetentionPolicy {
+            keep_newest: Some(1),
+            ..Default::default()
+        });
+
+        // Report mode leaves everything in place
+        crate::retention::run_once(&data).await;
+        assert!(tv.join("Show 1 (2021)").join("Season 1").exists());
+
+        data.categories[1].retention.as_mut().unwrap().mode = config::RetentionMode::Enforce;
+        crate::retention::run_once(&data).await;
+        assert!(!tv.join("Show 1 (2021)").join("Season 1").exists());
+        assert!(tv.join("Show 1 (2021)").join("Season 2").exists());
+        assert!(tv.join("Show 2 (2022)").join("Season 1").exists());
+        assert!(!tv.join("Show 3 (2023)").join("Season 2").exists());
+        assert!(tv.join("Show 3 (2023)").join("Season 3").exists());
+        assert!(
+            fs::read_to_string(tv.join(".stignore"))
+                .unwrap()
+                .contains("/Show 1 (2021)/Season 1")
+        );
+
+        let server = TestServer::new(create_test_router(data)).unwrap();
+        let response = server
+            .get("/api/v1/retention")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: RetentionReportResponse = response.json();
+        assert_eq!(json.runs.len(), 2);
+
+        let enforced = &json.runs[0];
+        assert_eq!(enforced.category_id, "tv");
+        assert_eq!(enforced.mode, config::RetentionMode::Enforce);
+        assert_eq!(enforced.actions.len(), 3);
+        assert!(
+            enforced
+                .actions
+                .iter()
+                .all(|action| action.status == crate::retention::RetentionStatus::Deleted)
+        );
+
+        let reported = &json.runs[1];
+        assert_eq!(reported.actions.len(), 3);
+        assert!(
+            reported
+                .actions
+                .iter()
+                .all(|action| action.status == crate::retention::RetentionStatus::Reported)
+        );
+    }
+
+    // Duplicate detection tests
+    #[tokio::test]
+    async fn test_duplicates_across_categories() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        // Stage a copy of a movie in the TV category
+        fs::copy(
+            temp_dir
+                .path()
+                .join("movies")
+                .join("Movie 1 (2023)")
+                .join("Movie 1 (2023).mkv"),
+            temp_dir.path().join("tv").join("Movie 1 (2023).mkv"),
+        )
+        .unwrap();
+
+        let response = server
+            .get("/api/v1/duplicates")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: DuplicatesResponse = response.json();
+        assert_eq!(json.groups.len(), 2);
+
+        // Every test episode has the same content
+        assert_eq!(json.groups[0].files.len(), 12);
+        assert_eq!(json.groups[0].reclaimable_bytes, 20 * 11);
+
+        let movie_group = &json.groups[1];
+        assert_eq!(movie_group.files.len(), 2);
+        assert_eq!(movie_group.files[0].category_id, "movies");
+        assert_eq!(
+            movie_group.files[0].path,
+            vec!["Movie 1 (2023)", "Movie 1 (2023).mkv"]
+        );
+        assert_eq!(movie_group.files[1].category_id, "tv");
+        assert_eq!(movie_group.reclaimable_bytes, 20);
+
+        assert_eq!(json.reclaimable_bytes, 20 * 12);
+    }
+
+    // Unicode normalization and case-insensitive matching tests
+    #[tokio::test]
+    async fn test_post_item_info_nfd_name() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        // macOS peers write decomposed (NFD) names
+        fs::create_dir_all(temp_dir.path().join("movies").join("Ame\u{301}lie (2001)")).unwrap();
+
+        let request_body = ItemInfoRequest {
+            item_path: vec![MOVIES_ID.to_string(), "Am\u{e9}lie (2001)".to_string()],
+            raw_components: vec![],
+        };
+
+        let response = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: ItemInfoResponse = response.json();
+        assert_eq!(json.item.name, "Ame\u{301}lie (2001)");
+    }
+
+    #[tokio::test]
+    async fn test_post_ignore_status_nfd_rule() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        std::fs::write(&stignore_path, "/Ame\u{301}lie (2001)\n").unwrap();
+
+        let request_body = IgnoreStatusRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Am\u{e9}lie (2001)".to_string()],
+            raw_components: vec![],
+        };
+
+        let response = server
+            .post("/api/v1/ignore-status")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let json: IgnoreStatusResponse = response.json();
+        assert!(json.ignored);
+    }
+
+    #[tokio::test]
+    async fn test_post_delete_nfd_name() {
+        let (server, temp_dir) = setup_test_server().await;
+
+        let movie_dir = temp_dir.path().join("movies").join("Ame\u{301}lie (2001)");
+        fs::create_dir_all(&movie_dir).unwrap();
+
+        let request_body = DeleteRequest {
+            category_id: MOVIES_ID.to_string(),
+            folder_path: vec!["Am\u{e9}lie (2001)".to_string()],
+            raw_components: vec![],
+            force: true,
+        };
+
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&request_body)
+            .await;
+        response.assert_status(StatusCode::OK);
+        assert!(!movie_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_category() {
+        let (server, temp_dir) = setup_case_insensitive_test_server().await;
+
+        let response = server
+            .post("/api/v1/items")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&ItemInfoRequest {
+                item_path: vec![MOVIES_ID.to_string(), "movie 1 (2023)".to_string()],
+                raw_components: vec![],
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        // New rules carry Syncthing's case-insensitive prefix
+        let response = server
+            .post("/api/v1/ignore")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["Movie 1 (2023)".to_string()],
+                raw_components: vec![],
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+
+        let stignore_path = temp_dir.path().join("movies").join(".stignore");
+        let content = std::fs::read_to_string(&stignore_path).unwrap();
+        assert!(content.contains("(?i)/Movie 1 (2023)"));
+
+        let response = server
+            .post("/api/v1/ignore-status")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&IgnoreStatusRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["MOVIE 1 (2023)".to_string()],
+                raw_components: vec![],
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        let json: IgnoreStatusResponse = response.json();
+        assert!(json.ignored);
+
+        let response = server
+            .post("/api/v1/delete")
+            .add_header("X-API-Key", "550e8400-e29b-41d4-a716-446655440000")
+            .json(&DeleteRequest {
+                category_id: MOVIES_ID.to_string(),
+                folder_path: vec!["MOVIE 1 (2023)".to_string()],
+                raw_components: vec![],
+                force: true,
+            })
+            .await;
+        response.assert_status(StatusCode::OK);
+        assert!(
+            !temp_dir
+                .path()
+                .join("movies")
+                .join("Movie 1 (2023)")
+                .exists()
+        );
+    }
+
     #[tokio::test]
     async fn test_syncthing_system_files_filtered() {
         let (server, _temp_dir) = setup_test_server().await;
@@ -1190,6 +3256,7 @@ mod tests {
         // Test that Syncthing system files (.st*) are filtered out from listing
         let request_body = ItemInfoRequest {
             item_path: vec![MOVIES_ID.to_string()],
+            raw_components: vec![],
         };
 
         let response = server